@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::rc::Rc;
+
+use sdl2::rect::Rect;
+use sdl2::render::{Renderer, Texture};
+
+use sprite::{Sprite, StaticSprite};
+use game_object::GameObject;
+use component::{Renderable, Velocity, Look};
+use item_components::Item;
+use level::Tile;
+use map::Map;
+
+
+// how long a bumped block moves up and down, and how high it goes.
+const BUMP_TIME : f64 = 200.0;
+const BUMP_HEIGHT : f64 = 10.0;
+
+// how fast the pieces of a shattered block fly apart, and how often they
+// turn over.
+const DEBRIS_SPEED_X : f32 = 1.5;
+const DEBRIS_SPEED_Y : f32 = 7.0;
+const DEBRIS_FLIP_TIME : f64 = 100.0;
+
+
+// What came of hitting a block.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hit {
+    pub contents: Option<Item>,
+    pub shattered: bool
+}
+
+
+// The blocks of a map that hold items, and the ones that are bumped up.
+pub struct Blocks {
+    contents: HashMap<(i32, i32), (Item, u32)>,
+    bumps: HashMap<(i32, i32), f64>
+}
+
+impl Blocks {
+    // Reads the `block` objects of `map`, which put `count` of the `contents`
+    // item in the block at their position, instead of what its type holds.
+    pub fn new(map: &Map) -> Result<Blocks, String> {
+        let mut contents = HashMap::new();
+
+        for object in map.objects.iter().filter(|object| object.kind == "block") {
+            let position = ((object.x / map.tile_width() as f32) as i32, (object.y / map.tile_height() as f32) as i32);
+
+            let item = match object.properties.get("contents").map(|name| (name, Item::from_name(name))) {
+                Some((_, Some(item))) => item,
+                Some((name, None)) => return Err(format!("block at {:?}: unknown item `{}`", position, name)),
+                None => return Err(format!("block at {:?}: expected `contents`", position))
+            };
+
+            let count = match object.properties.get("count") {
+                Some(count) => count.parse().map_err(|_| format!("block at {:?}: expected a number, found `{}`", position, count))?,
+                None => 1
+            };
+
+            contents.insert(position, (item, count));
+        }
+
+        Ok(Blocks {
+            contents: contents,
+            bumps: HashMap::new()
+        })
+    }
+
+    // Hits the block at `x`, `y` from below, and bumps it up. Breakable blocks
+    // that are empty are shattered by `strong` hits instead, and tiles that
+    // are not blocks do not react at all.
+    pub fn hit(&mut self, map: &mut Map, x: i32, y: i32, strong: bool) -> Option<Hit> {
+        let kind = match map.kind_at(x, y) {
+            Some(kind) => kind.clone(),
+            None => return None
+        };
+
+        let (contents, left) = match self.contents.get_mut(&(x, y)) {
+            Some(&mut (item, ref mut count)) => {
+                *count = count.saturating_sub(1);
+
+                (Some(item), *count)
+            },
+            None => (kind.contents, 0)
+        };
+
+        if contents.is_some() {
+            if left == 0 {
+                self.contents.remove(&(x, y));
+
+                map.replace_tile(x, y, Tile::Defined(Rc::new(kind.used_up())));
+            }
+        } else if !kind.breakable {
+            return None;
+        } else if strong {
+            self.bumps.remove(&(x, y));
+
+            map.replace_tile(x, y, Tile::Empty);
+
+            return Some(Hit { contents: None, shattered: true });
+        }
+
+        self.bumps.insert((x, y), 0.0);
+
+        Some(Hit { contents: contents, shattered: false })
+    }
+
+    pub fn update(&mut self, dt: f64) {
+        for time in self.bumps.values_mut() {
+            *time += dt;
+        }
+
+        self.bumps.retain(|_, time| *time < BUMP_TIME);
+    }
+
+    // How many pixels above its place the block at `x`, `y` is drawn.
+    pub fn offset(&self, x: i32, y: i32) -> i32 {
+        match self.bumps.get(&(x, y)) {
+            Some(time) => (BUMP_HEIGHT * (PI * time / BUMP_TIME).sin()) as i32,
+            None => 0
+        }
+    }
+}
+
+
+// The four pieces that the block at `x`, `y` shatters into. They fly off and
+// fall through everything.
+pub fn debris<'a>(x: f32, y: f32, size: u32, texture: &'a Texture) -> Vec<GameObject<'a>> {
+    let half = (size / 2) as f32;
+
+    [(0.0, 0.0, -1.0, 1.0), (half, 0.0, 1.0, 1.0), (0.0, half, -1.0, 0.7), (half, half, 1.0, 0.7)].iter().map(|&(left, top, direction, speed)| {
+        GameObject::new(x + left, y + top)
+            .with_size(size / 2, size / 2)
+            .with_velocity(Velocity { solid: false, ..Velocity::new(direction * DEBRIS_SPEED_X, -speed * DEBRIS_SPEED_Y) })
+            .with_graphics(Box::new(DebrisGraphicsComponent::new(texture)))
+    }).collect()
+}
+
+
+pub struct DebrisGraphicsComponent<'a> {
+    sprite: StaticSprite<'a>,
+    time: f64
+}
+
+impl<'a> DebrisGraphicsComponent<'a> {
+    pub fn new(texture: &'a Texture) -> DebrisGraphicsComponent<'a> {
+        DebrisGraphicsComponent {
+            sprite: StaticSprite::new(texture, 160, 0),
+            time: 0.0
+        }
+    }
+}
+
+impl<'a> Renderable for DebrisGraphicsComponent<'a> {
+    fn render(&mut self, _: &Look, elapsed: f64, renderer: &mut Renderer, destination: &Rect) {
+        self.time += elapsed;
+
+        let turn = (self.time / DEBRIS_FLIP_TIME) as u32 % 4;
+
+        self.sprite.flip_horizontal = turn == 1 || turn == 2;
+        self.sprite.flip_vertical = turn >= 2;
+        self.sprite.render(elapsed, renderer, destination);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use sdl2::rect::Rect;
+
+    use map::{Pass, Parallax, Object};
+    use test_util;
+
+    use super::*;
+
+    fn map() -> Map {
+        // a question block, a brick with three coins and a plain brick.
+        let mut map = Map::new(3, 1, 32, 32);
+
+        let mut question = test_util::solid("question");
+
+        question.contents = Some(Item::Mushroom);
+        question.used = Some(Rect::new(16, 0, 16, 16));
+
+        let mut brick = test_util::solid("brick");
+
+        brick.breakable = true;
+
+        let brick = Tile::Defined(Rc::new(brick));
+        let layer = map.add_layer(Pass::Collision, 3, Parallax::none());
+
+        layer.set_tile(0, 0, Tile::Defined(Rc::new(question)));
+        layer.set_tile(1, 0, brick.clone());
+        layer.set_tile(2, 0, brick);
+
+        let mut properties = HashMap::new();
+
+        properties.insert("contents".to_string(), "coin".to_string());
+        properties.insert("count".to_string(), "3".to_string());
+
+        map.objects.push(Object { name: String::new(), kind: "block".to_string(), x: 32.0, y: 0.0, width: 32.0, height: 32.0, properties: properties });
+
+        map
+    }
+
+    #[test]
+    fn blocks_hit() {
+        let mut map = map();
+        let mut blocks = Blocks::new(&map).unwrap();
+
+        let bumped = |contents| Some(Hit { contents: contents, shattered: false });
+
+        // the question block gives what its type holds, once.
+        assert_eq!(blocks.hit(&mut map, 0, 0, false), bumped(Some(Item::Mushroom)));
+        assert_eq!(map.kind_at(0, 0).unwrap().source, Rect::new(16, 0, 16, 16));
+        assert_eq!(blocks.hit(&mut map, 0, 0, false), None);
+
+        // bricks give what their object says, and strong hits only shatter
+        // empty ones.
+        for _ in 0..3 {
+            assert_eq!(blocks.hit(&mut map, 1, 0, true), bumped(Some(Item::Coin)));
+        }
+
+        assert_eq!(blocks.hit(&mut map, 1, 0, true), None);
+
+        assert_eq!(blocks.hit(&mut map, 2, 0, false), bumped(None));
+        assert_eq!(blocks.hit(&mut map, 2, 0, true), Some(Hit { contents: None, shattered: true }));
+        assert!(map.kind_at(2, 0).is_none());
+
+        // bumped blocks go up and come back down.
+        assert_eq!(blocks.offset(1, 0), 0);
+
+        blocks.update(BUMP_TIME / 2.0);
+
+        assert_eq!(blocks.offset(1, 0), BUMP_HEIGHT as i32);
+
+        blocks.update(BUMP_TIME / 2.0);
+
+        assert_eq!(blocks.offset(1, 0), 0);
+    }
+}
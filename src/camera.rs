@@ -24,27 +24,55 @@ impl Camera {
         let mut x = (object.x() + object.width() as i32 / 2) - (self.width as i32 / 2);
         let mut y = (object.y() + object.height() as i32 / 2) - (self.height as i32 / 2);
 
-        if x < self.bounding.x() {
-            x = self.bounding.x();
-        } else if x + self.width as i32 > self.bounding.width() as i32 {
-            x = (self.bounding.width() - self.width) as i32;
-        }
-
-        if y < self.bounding.y() {
-            y = self.bounding.y();
-        } else if y + self.height as i32 > self.bounding.height() as i32 {
-            y = (self.bounding.height() - self.height) as i32;
-        }
+        // keeps to the left and the top of areas smaller than the camera.
+        x = x.min(self.bounding.right() - self.width as i32).max(self.bounding.x());
+        y = y.min(self.bounding.bottom() - self.height as i32).max(self.bounding.y());
 
         self.x = x;
         self.y = y;
     }
 
+    // The area that the camera stays in, such as the part of the level that
+    // the player is in.
+    pub fn set_bounding(&mut self, bounding: Rect) {
+        self.bounding = bounding;
+    }
+
     pub fn to_relative_rect(&self, rect: &Rect) -> Rect {
         Rect::new(rect.x() - self.x, rect.y() - self.y, rect.width(), rect.height())
     }
 
+    pub fn to_parallax_rect(&self, scroll_x: f32, scroll_y: f32) -> Rect {
+        Rect::new((self.x as f32 * scroll_x) as i32, (self.y as f32 * scroll_y) as i32, self.width, self.height)
+    }
+
     pub fn to_rect(&self) -> Rect {
         Rect::new(self.x, self.y, self.width, self.height)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn camera_bounding() {
+        let mut camera = Camera::new(0, 0, 100, 50, Rect::new(0, 0, 400, 200));
+
+        camera.center(&Rect::new(390, 10, 10, 10));
+
+        assert_eq!(camera.to_rect(), Rect::new(300, 0, 100, 50));
+
+        // an area that does not start at the origin, and one that is smaller
+        // than the camera.
+        camera.set_bounding(Rect::new(1000, 300, 200, 100));
+        camera.center(&Rect::new(1010, 390, 10, 10));
+
+        assert_eq!(camera.to_rect(), Rect::new(1000, 350, 100, 50));
+
+        camera.set_bounding(Rect::new(500, 0, 60, 40));
+        camera.center(&Rect::new(550, 30, 10, 10));
+
+        assert_eq!(camera.to_rect(), Rect::new(500, 0, 100, 50));
+    }
+}
@@ -0,0 +1,527 @@
+use sdl2::rect::Rect;
+
+use tile::{self, Layer, RayHit};
+use level::Tile;
+use map::Map;
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Slope {
+    Up,
+    Down,
+    UpLow,
+    UpHigh,
+    DownHigh,
+    DownLow
+}
+
+impl Slope {
+    pub fn from_name(name: &str) -> Option<Slope> {
+        match name {
+            "up" => Some(Slope::Up),
+            "down" => Some(Slope::Down),
+            "up-low" => Some(Slope::UpLow),
+            "up-high" => Some(Slope::UpHigh),
+            "down-high" => Some(Slope::DownHigh),
+            "down-low" => Some(Slope::DownLow),
+            _ => None
+        }
+    }
+
+    // Height of the floor at `offset`, both as fractions of the tile where
+    // 0.0 is the left edge and the bottom of the tile.
+    pub fn height(&self, offset: f32) -> f32 {
+        let offset = offset.clamp(0.0, 1.0);
+
+        match *self {
+            Slope::Up => offset,
+            Slope::Down => 1.0 - offset,
+            Slope::UpLow => offset / 2.0,
+            Slope::UpHigh => 0.5 + offset / 2.0,
+            Slope::DownHigh => 1.0 - offset / 2.0,
+            Slope::DownLow => 0.5 - offset / 2.0
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Shape {
+    Empty,
+    Solid,
+    Platform,
+    Slope(Slope)
+}
+
+
+pub trait Grid {
+    fn tile_width(&self) -> u32;
+    fn tile_height(&self) -> u32;
+    fn find_intersecting(&self, rect: &Rect) -> Option<Rect>;
+    fn shape(&self, x: i32, y: i32) -> Shape;
+
+    // The first solid tile between two points, in pixels.
+    fn raycast(&self, from: (f32, f32), to: (f32, f32)) -> Option<RayHit> {
+        tile::raycast(self.tile_width(), self.tile_height(), from, to, |x, y| self.shape(x, y) == Shape::Solid)
+    }
+
+    fn has_line_of_sight(&self, a: (f32, f32), b: (f32, f32)) -> bool {
+        self.raycast(a, b).is_none()
+    }
+}
+
+impl Grid for Layer<Tile> {
+    fn tile_width(&self) -> u32 {
+        Layer::tile_width(self)
+    }
+
+    fn tile_height(&self) -> u32 {
+        Layer::tile_height(self)
+    }
+
+    fn find_intersecting(&self, rect: &Rect) -> Option<Rect> {
+        Layer::find_intersecting(self, rect)
+    }
+
+    fn shape(&self, x: i32, y: i32) -> Shape {
+        self.try_get_tile(x, y).map(|tile| tile.shape()).unwrap_or(Shape::Empty)
+    }
+}
+
+impl Grid for Map {
+    fn tile_width(&self) -> u32 {
+        Map::tile_width(self)
+    }
+
+    fn tile_height(&self) -> u32 {
+        Map::tile_height(self)
+    }
+
+    fn find_intersecting(&self, rect: &Rect) -> Option<Rect> {
+        Map::find_intersecting(self, rect)
+    }
+
+    fn shape(&self, x: i32, y: i32) -> Shape {
+        Map::shape(self, x, y)
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Body {
+    pub x: f32,
+    pub y: f32,
+    pub width: u32,
+    pub height: u32
+}
+
+impl Body {
+    pub fn new(x: f32, y: f32, width: u32, height: u32) -> Body {
+        Body {
+            x: x,
+            y: y,
+            width: width,
+            height: height
+        }
+    }
+
+    pub fn to_rect(self) -> Rect {
+        Rect::new(self.x as i32, self.y as i32, self.width, self.height)
+    }
+}
+
+
+#[derive(Debug, PartialEq)]
+pub struct CollisionResult {
+    pub x: f32,
+    pub y: f32,
+    pub left: bool,
+    pub right: bool,
+    pub top: bool,
+    pub bottom: bool,
+    pub tiles: Vec<(i32, i32)>
+}
+
+
+struct Sweep {
+    distance: f32,
+    blocked: bool,
+    tiles: Vec<(i32, i32)>
+}
+
+// Moves the edge at `start` along one axis by `delta`, stopping at the first
+// solid tile in any of the `lanes` the body covers. `is_solid` is called with
+// the tile coordinate along the axis and the lane.
+fn sweep<F: Fn(i32, i32) -> bool>(start: f32, length: u32, tile_size: u32, first: i32, lanes: (i32, i32), delta: f32, is_solid: F) -> Sweep {
+    let mut result = Sweep {
+        distance: delta,
+        blocked: false,
+        tiles: Vec::new()
+    };
+
+    let edge = if delta > 0.0 { start + length as f32 } else { start };
+
+    for lane in lanes.0..lanes.1 {
+        let mut along = first;
+
+        loop {
+            let t = if delta > 0.0 {
+                (along * tile_size as i32) as f32 - edge
+            } else {
+                (along * tile_size as i32 + tile_size as i32) as f32 - edge
+            };
+
+            if (delta > 0.0 && t > result.distance) || (delta < 0.0 && t < result.distance) {
+                break;
+            }
+
+            if is_solid(along, lane) {
+                if !result.blocked || t != result.distance {
+                    result.tiles.clear();
+                }
+
+                result.distance = t;
+                result.blocked = true;
+                result.tiles.push((along, lane));
+            }
+
+            along += if delta > 0.0 { 1 } else { -1 };
+        }
+    }
+
+    result
+}
+
+// The row of pixels just below `rect`, where whatever it stands on is.
+pub fn below(rect: &Rect) -> Rect {
+    Rect::new(rect.x(), rect.y() + rect.height() as i32, rect.width(), 1)
+}
+
+// One-way platforms only stop bodies that are falling onto them from above,
+// and not at all when `drop_through` is set.
+pub fn move_and_collide<G: Grid>(grid: &G, body: &Body, dx: f32, dy: f32, drop_through: bool) -> CollisionResult {
+    let mut result = CollisionResult {
+        x: body.x + dx,
+        y: body.y + dy,
+        left: false,
+        right: false,
+        top: false,
+        bottom: false,
+        tiles: Vec::new()
+    };
+
+    // bodies on a slope may step onto a block at the top of it.
+    let step = match find_slope_floor(grid, body, 1.0) {
+        Some((floor, _)) if (floor - (body.y + body.height as f32)).abs() <= 1.0 => dx.abs(),
+        _ => 0.0
+    };
+
+    if dx != 0.0 {
+        let raised = Body::new(body.x, body.y - step, body.width, body.height);
+
+        if let Some(intersect) = grid.find_intersecting(&raised.to_rect()) {
+            let lanes = (intersect.y(), intersect.y() + intersect.height() as i32);
+
+            let sweep = sweep(body.x, body.width, grid.tile_width(), intersect.x(), lanes, dx, |column, row| {
+                grid.shape(column, row) == Shape::Solid
+            });
+
+            result.x = body.x + sweep.distance;
+            result.left = sweep.blocked && dx < 0.0;
+            result.right = sweep.blocked && dx > 0.0;
+            result.tiles.extend(sweep.tiles);
+        }
+    }
+
+    if dy != 0.0 {
+        let moved = Body::new(result.x, body.y, body.width, body.height);
+
+        if let Some(intersect) = grid.find_intersecting(&moved.to_rect()) {
+            let lanes = (intersect.x(), intersect.x() + intersect.width() as i32);
+            let bottom = body.y + body.height as f32;
+            let tile_height = grid.tile_height();
+
+            let sweep = sweep(body.y, body.height, tile_height, intersect.y(), lanes, dy, |row, column| {
+                match grid.shape(column, row) {
+                    Shape::Solid => true,
+                    Shape::Platform => dy > 0.0 && !drop_through && bottom <= (row * tile_height as i32) as f32,
+                    Shape::Slope(_) | Shape::Empty => false
+                }
+            });
+
+            result.y = body.y + sweep.distance;
+            result.top = sweep.blocked && dy < 0.0;
+            result.bottom = sweep.blocked && dy > 0.0;
+            result.tiles.extend(sweep.tiles.into_iter().map(|(row, column)| (column, row)));
+        }
+    }
+
+    let moved = Body::new(result.x, result.y, body.width, body.height);
+
+    if let Some((floor, tile)) = find_slope_floor(grid, &moved, dx.abs() + dy.max(0.0)) {
+        let bottom = result.y + body.height as f32;
+
+        if bottom - floor > dx.abs() + dy.abs() + 1.0 {
+            // walked into the steep side of a slope.
+            result.x = body.x;
+            result.left = dx < 0.0;
+            result.right = dx > 0.0;
+            result.tiles.push(tile);
+        } else if bottom > floor || (dy >= 0.0 && !result.top && !result.bottom) {
+            result.y = floor - body.height as f32;
+            result.bottom = dy >= 0.0;
+            result.tiles.push(tile);
+        }
+    }
+
+    result
+}
+
+// Finds the highest point of any slope below the bottom edge of `body`, from
+// one tile above the edge down to `snap` pixels below it, so bodies walking up
+// a slope are lifted onto it and bodies walking down stay on it.
+fn find_slope_floor<G: Grid>(grid: &G, body: &Body, snap: f32) -> Option<(f32, (i32, i32))> {
+    let tile_width = grid.tile_width() as f32;
+    let tile_height = grid.tile_height() as f32;
+
+    let left = body.x;
+    let right = body.x + body.width as f32;
+    let bottom = body.y + body.height as f32;
+
+    let area = Rect::new(left as i32, (bottom - tile_height) as i32, body.width, (tile_height + snap).ceil() as u32);
+
+    let intersect = grid.find_intersecting(&area)?;
+
+    let mut floor: Option<(f32, (i32, i32))> = None;
+
+    for row in intersect.y()..(intersect.y() + intersect.height() as i32) {
+        for column in intersect.x()..(intersect.x() + intersect.width() as i32) {
+            let slope = match grid.shape(column, row) {
+                Shape::Slope(slope) => slope,
+                _ => continue
+            };
+
+            let tile_left = column as f32 * tile_width;
+
+            let height = slope.height((left - tile_left) / tile_width).max(slope.height((right - tile_left) / tile_width));
+            let y = (row + 1) as f32 * tile_height - height * tile_height;
+
+            if y > bottom + snap || floor.map(|(highest, _)| y >= highest).unwrap_or(false) {
+                continue;
+            }
+
+            floor = Some((y, (column, row)));
+        }
+    }
+
+    floor
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::rect::Rect;
+
+    use std::rc::Rc;
+
+    use tile::Layer;
+    use level::Tile;
+    use registry::TileType;
+
+    use super::*;
+
+    fn tile(property: &str, value: &str) -> Tile {
+        let mut kind = TileType::new("test", Rect::new(0, 0, 16, 16));
+
+        kind.set_property(property, value).unwrap();
+
+        Tile::Defined(Rc::new(kind))
+    }
+
+    fn layer() -> Layer<Tile> {
+        // . . . .
+        // . . . #
+        // . . . #
+        // # # # #
+        let mut layer = Layer::new(4, 4, 10, 10, Tile::Empty);
+
+        for x in 0..4 {
+            layer.set_tile(x, 3, tile("solid", "true"));
+        }
+
+        layer.set_tile(3, 1, tile("solid", "true"));
+        layer.set_tile(3, 2, tile("solid", "true"));
+
+        layer
+    }
+
+    #[test]
+    fn move_and_collide_free() {
+        let result = move_and_collide(&layer(), &Body::new(2.0, 2.0, 5, 5), 1.5, 2.5, false);
+
+        assert_eq!(result, CollisionResult { x: 3.5, y: 4.5, left: false, right: false, top: false, bottom: false, tiles: vec![] });
+    }
+
+    #[test]
+    fn move_and_collide_floor() {
+        // lands on the floor.
+        let result = move_and_collide(&layer(), &Body::new(12.0, 22.0, 5, 5), 0.0, 8.0, false);
+
+        assert_eq!((result.y, result.bottom), (25.0, true));
+        assert_eq!(result.tiles, vec![(1, 3)]);
+
+        // stays on the floor.
+        let result = move_and_collide(&layer(), &Body::new(12.0, 25.0, 5, 5), 0.0, 0.3, false);
+
+        assert_eq!((result.y, result.bottom), (25.0, true));
+
+        // touches two floor tiles.
+        let result = move_and_collide(&layer(), &Body::new(8.0, 20.0, 5, 5), 0.0, 8.0, false);
+
+        assert_eq!((result.y, result.bottom), (25.0, true));
+        assert_eq!(result.tiles, vec![(0, 3), (1, 3)]);
+    }
+
+    #[test]
+    fn move_and_collide_wall() {
+        // walks into the wall.
+        let result = move_and_collide(&layer(), &Body::new(22.0, 12.0, 5, 10), 4.0, 0.0, false);
+
+        assert_eq!((result.x, result.right, result.left), (25.0, true, false));
+        assert_eq!(result.tiles, vec![(3, 1), (3, 2)]);
+
+        // walks away from the wall.
+        let result = move_and_collide(&layer(), &Body::new(25.0, 15.0, 5, 5), -4.0, 0.0, false);
+
+        assert_eq!((result.x, result.right, result.left), (21.0, false, false));
+
+        // walks along the floor into the wall and lands at the same time.
+        let result = move_and_collide(&layer(), &Body::new(22.0, 24.0, 5, 5), 4.0, 2.0, false);
+
+        assert_eq!((result.x, result.y, result.right, result.bottom), (25.0, 25.0, true, true));
+    }
+
+    #[test]
+    fn move_and_collide_platform() {
+        let mut layer = layer();
+
+        layer.set_tile(1, 1, tile("one-way", "true"));
+
+        // jumps up through the platform.
+        let result = move_and_collide(&layer, &Body::new(12.0, 22.0, 5, 5), 0.0, -8.0, false);
+
+        assert_eq!((result.y, result.top), (14.0, false));
+
+        // falls through the platform while overlapping it.
+        let result = move_and_collide(&layer, &Body::new(12.0, 14.0, 5, 5), 0.0, 4.0, false);
+
+        assert_eq!((result.y, result.bottom), (18.0, false));
+
+        // walks through the platform.
+        let result = move_and_collide(&layer, &Body::new(2.0, 12.0, 5, 5), 8.0, 0.0, false);
+
+        assert_eq!((result.x, result.right), (10.0, false));
+
+        // lands on the platform.
+        let result = move_and_collide(&layer, &Body::new(12.0, 2.0, 5, 5), 0.0, 6.0, false);
+
+        assert_eq!((result.y, result.bottom), (5.0, true));
+        assert_eq!(result.tiles, vec![(1, 1)]);
+
+        // drops through the platform.
+        let result = move_and_collide(&layer, &Body::new(12.0, 5.0, 5, 5), 0.0, 0.5, true);
+
+        assert_eq!((result.y, result.bottom), (5.5, false));
+
+        let result = move_and_collide(&layer, &Body::new(12.0, 5.5, 5, 5), 0.0, 0.5, false);
+
+        assert_eq!((result.y, result.bottom), (6.0, false));
+    }
+
+    #[test]
+    fn move_and_collide_ceiling() {
+        let mut layer = layer();
+
+        layer.set_tile(1, 0, tile("solid", "true"));
+
+        let result = move_and_collide(&layer, &Body::new(12.0, 14.0, 5, 5), 0.0, -8.0, false);
+
+        assert_eq!((result.y, result.top, result.bottom), (10.0, true, false));
+        assert_eq!(result.tiles, vec![(1, 0)]);
+    }
+
+    fn slopes() -> Layer<Tile> {
+        // . . . . . .
+        // . . . . . .
+        // . / # \ / .
+        // # # # # # #
+        let mut layer = Layer::new(6, 4, 10, 10, Tile::Empty);
+
+        for x in 0..6 {
+            layer.set_tile(x, 3, tile("solid", "true"));
+        }
+
+        layer.set_tile(1, 2, tile("slope", "up"));
+        layer.set_tile(2, 2, tile("solid", "true"));
+        layer.set_tile(3, 2, tile("slope", "down"));
+        layer.set_tile(4, 2, tile("slope", "up"));
+
+        layer
+    }
+
+    #[test]
+    fn move_and_collide_slope() {
+        // walks up the slope.
+        let result = move_and_collide(&slopes(), &Body::new(7.5, 22.5, 5, 5), 2.5, 0.0, false);
+
+        assert_eq!((result.x, result.y, result.bottom), (10.0, 20.0, true));
+        assert_eq!(result.tiles, vec![(1, 2)]);
+
+        // steps from the top of the slope onto the block.
+        let result = move_and_collide(&slopes(), &Body::new(12.5, 17.5, 5, 5), 4.0, 0.0, false);
+
+        assert_eq!((result.x, result.y, result.right), (16.5, 15.0, false));
+
+        // stays on the slope while walking down.
+        let result = move_and_collide(&slopes(), &Body::new(30.0, 15.0, 5, 5), 2.5, 0.5, false);
+
+        assert_eq!((result.x, result.y, result.bottom), (32.5, 17.5, true));
+
+        // walks into the steep side of the slope.
+        let result = move_and_collide(&slopes(), &Body::new(50.0, 25.0, 5, 5), -2.5, 0.0, false);
+
+        assert_eq!((result.x, result.y, result.left), (50.0, 25.0, true));
+
+        // lands on the slope.
+        let result = move_and_collide(&slopes(), &Body::new(12.5, 12.0, 5, 5), 0.0, 8.0, false);
+
+        assert_eq!((result.y, result.bottom), (17.5, true));
+
+        // walks up a shallow slope.
+        let mut layer = slopes();
+
+        layer.set_tile(1, 2, tile("slope", "up-low"));
+        layer.set_tile(2, 2, tile("slope", "up-high"));
+
+        let result = move_and_collide(&layer, &Body::new(7.5, 23.75, 5, 5), 2.5, 0.0, false);
+
+        assert_eq!((result.x, result.y), (10.0, 22.5));
+
+        let result = move_and_collide(&layer, &Body::new(15.0, 20.0, 5, 5), 2.5, 0.0, false);
+
+        assert_eq!((result.x, result.y), (17.5, 18.75));
+    }
+
+    #[test]
+    fn layer_line_of_sight() {
+        let mut layer = layer();
+
+        layer.set_tile(1, 1, tile("one-way", "true"));
+
+        // sees through the air and platforms, but not through the wall.
+        assert!(layer.has_line_of_sight((5.0, 5.0), (25.0, 25.0)));
+        assert!(layer.has_line_of_sight((5.0, 15.0), (25.0, 15.0)));
+        assert!(!layer.has_line_of_sight((5.0, 15.0), (35.0, 15.0)));
+
+        assert_eq!(layer.raycast((5.0, 15.0), (35.0, 15.0)).map(|hit| hit.tile), Some((3, 1)));
+    }
+}
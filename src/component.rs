@@ -1,12 +1,260 @@
 use sdl2::rect::Rect;
 use sdl2::render::Renderer;
 
-use game_object::GameObject;
+use game_object::{Pose, Power};
+use map::Map;
+use world::Entity;
+use contact::{Collider, Side};
+use collision::Body;
+use item_components::Item;
+use projectile_components::Projectile;
 
+// how long a tick of the game lasts, in milliseconds. Speeds are in pixels
+// and accelerations in fractions per tick.
+pub const TICK : f64 = 10.0;
+
+// how fast objects fall, unless they say otherwise.
+pub const GRAVITY : f32 = 0.3;
+pub const TERMINAL_SPEED : f32 = 8.0;
+
+// What the player asks for, jump, jump_released and action_pressed stay set
+// until a tick has seen them.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct Input {
+    pub left: bool,
+    pub right: bool,
+    pub up: bool,
+    pub down: bool,
+    pub action: bool,
+    pub action_pressed: bool,
+    pub jump: bool,
+    pub jump_released: bool
+}
+
+// Everything a system may look at during a tick, `dt` is in milliseconds.
+pub struct Context<'c> {
+    pub map: &'c Map,
+    pub input: Input,
+    pub player: Option<Rect>,
+    pub dt: f64
+}
+
+// What happened to an object during a tick, for the game to react to.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Event {
+    Jumped,
+    Landed,
+    Touched(Entity, Side),
+    Stomped,
+    Kicked,
+    Killed,
+    Died,
+    Hurt,
+    Bumped(i32, i32),
+    Shattered,
+    Collected(Item),
+    Shot(Projectile)
+}
+
+// Another object that an object touched, `side` is the side of the other
+// object and `layer` its collision layer. There is no `entity` when it was
+// touched by the map, like a block bumped from below.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Touch {
+    pub entity: Option<Entity>,
+    pub side: Side,
+    pub layer: u32,
+    pub rect: Rect
+}
+
+
+// Where an object is, how large it is and which way it faces, -1 for left
+// and 1 for right.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Transform {
+    pub x: f32,
+    pub y: f32,
+    pub w: u32,
+    pub h: u32,
+    pub facing: f32
+}
+
+impl Transform {
+    pub fn new(x: f32, y: f32, w: u32, h: u32) -> Transform {
+        Transform {
+            x: x,
+            y: y,
+            w: w,
+            h: h,
+            facing: 1.0
+        }
+    }
+
+    pub fn to_body(self) -> Body {
+        Body::new(self.x, self.y, self.w, self.h)
+    }
+
+    pub fn to_rect(self) -> Rect {
+        Rect::new(self.x as i32, self.y as i32, self.w, self.h)
+    }
+}
+
+
+// The sides of an object that the map stopped during the last tick.
+#[derive(Clone, Copy, PartialEq, Default, Debug)]
+pub struct Blocked {
+    pub left: bool,
+    pub right: bool,
+    pub top: bool,
+    pub bottom: bool
+}
+
+// How an object moves. The physics system pulls it down by `gravity` up to
+// `terminal` speed, and moves it by `dx`, `dy` every tick, through the map
+// unless it is `solid`. `drop_through` drops it through the platform below
+// for one tick.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Velocity {
+    pub dx: f32,
+    pub dy: f32,
+    pub gravity: f32,
+    pub terminal: f32,
+    pub solid: bool,
+    pub drop_through: bool,
+    pub on_ground: bool,
+    pub blocked: Blocked
+}
+
+impl Velocity {
+    pub fn new(dx: f32, dy: f32) -> Velocity {
+        Velocity {
+            dx: dx,
+            dy: dy,
+            gravity: GRAVITY,
+            terminal: TERMINAL_SPEED,
+            solid: true,
+            drop_through: false,
+            on_ground: false,
+            blocked: Blocked::default()
+        }
+    }
+
+    // Stays where it is, until something sets it moving.
+    pub fn still() -> Velocity {
+        Velocity {
+            gravity: 0.0,
+            solid: false,
+            ..Velocity::new(0.0, 0.0)
+        }
+    }
+}
+
+
+// How much an object takes. A hit takes away its power, or kills it when it
+// has none, and it can not be hit again for `recovery` milliseconds after
+// losing its power. Only the player is ever powered up.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Health {
+    pub power: Power,
+    pub invincible: f64,
+    pub dead: bool,
+    recovery: f64,
+    hit: bool,
+    fatal: bool
+}
+
+impl Health {
+    pub fn new(recovery: f64) -> Health {
+        Health {
+            power: Power::Small,
+            invincible: 0.0,
+            dead: false,
+            recovery: recovery,
+            hit: false,
+            fatal: false
+        }
+    }
+
+    pub fn hit(&mut self) {
+        self.hit = true;
+    }
+
+    // Kills the object whatever its power, like a hazard does.
+    pub fn kill(&mut self) {
+        self.fatal = true;
+    }
+
+    // Counts down `dt` and takes the hits since the last update, returns
+    // whether they hurt or killed the object.
+    pub fn update(&mut self, dt: f64) -> Option<Event> {
+        let (hit, fatal) = (self.hit, self.fatal);
+
+        self.hit = false;
+        self.fatal = false;
+        self.invincible = (self.invincible - dt).max(0.0);
+
+        if self.dead {
+            return None;
+        }
+
+        let hurt = hit && self.invincible <= 0.0;
+
+        if fatal || (hurt && self.power == Power::Small) {
+            self.dead = true;
+
+            Some(Event::Killed)
+        } else if hurt {
+            self.power = Power::Small;
+            self.invincible = self.recovery;
+
+            Some(Event::Hurt)
+        } else {
+            None
+        }
+    }
+}
+
+
+// The parts of an entity that its AI looks after while it runs.
+pub struct Actor<'w> {
+    pub transform: &'w mut Transform,
+    pub velocity: &'w mut Velocity,
+    pub collider: &'w mut Option<Collider>,
+    pub pose: &'w mut Pose,
+    pub health: Option<&'w mut Health>,
+    pub removed: &'w mut bool
+}
+
+// What the graphics of an object show.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Look {
+    pub pose: Pose,
+    pub power: Power,
+    pub facing: f32,
+    pub h: u32,
+    pub dx: f32,
+    pub on_ground: bool
+}
+
+// The AI of an object, which decides how it moves and reacts to what it
+// touches, to being hurt and to what it collects.
 pub trait Updatable {
-    fn update(&self, &GameObject);
+    fn update(&mut self, &mut Actor, &Context) -> Vec<Event>;
+
+    fn touch(&mut self, _: &mut Actor, _: &Touch, _: &Context) -> Vec<Event> {
+        Vec::new()
+    }
+
+    fn collect(&mut self, _: &mut Actor, _: Item) {
+    }
+
+    fn hurt(&mut self, _: &mut Actor) {
+    }
+
+    fn kill(&mut self, _: &mut Actor) {
+    }
 }
 
 pub trait Renderable {
-    fn render(&self, &GameObject, f64, &mut Renderer, &Rect);
+    fn render(&mut self, &Look, f64, &mut Renderer, &Rect);
 }
@@ -0,0 +1,195 @@
+use std::collections::{HashMap, HashSet};
+
+use sdl2::rect::Rect;
+
+
+// Collision layers, an object is on `layer` and touches objects on `mask`.
+// Projectiles kill enemies, kickable objects are shells lying still, blocks
+// are tiles bumped from below and shots are enemy projectiles that hurt the
+// player.
+pub const PLAYER : u32 = 1;
+pub const ENEMY : u32 = 2;
+pub const PICKUP : u32 = 4;
+pub const PROJECTILE : u32 = 8;
+pub const KICKABLE : u32 = 16;
+pub const BLOCK : u32 = 32;
+pub const SHOT : u32 = 64;
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Collider {
+    pub layer: u32,
+    pub mask: u32
+}
+
+impl Collider {
+    pub fn new(layer: u32, mask: u32) -> Collider {
+        Collider {
+            layer: layer,
+            mask: mask
+        }
+    }
+
+    // Two objects touch when either of them is looking for the other.
+    pub fn interacts(&self, other: &Collider) -> bool {
+        self.mask & other.layer != 0 || other.mask & self.layer != 0
+    }
+}
+
+
+// The side of the other object that was touched, so `Top` means landing on
+// top of it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Side {
+    Top,
+    Bottom,
+    Left,
+    Right
+}
+
+impl Side {
+    pub fn opposite(&self) -> Side {
+        match *self {
+            Side::Top => Side::Bottom,
+            Side::Bottom => Side::Top,
+            Side::Left => Side::Right,
+            Side::Right => Side::Left
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Contact<T> {
+    pub a: T,
+    pub b: T,
+    pub side: Side
+}
+
+
+// A uniform grid of cells that lists the objects overlapping each of them,
+// so that only objects sharing a cell are tested against each other.
+pub struct SpatialHash {
+    cell_size: u32,
+    cells: HashMap<(i32, i32), Vec<usize>>
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: u32) -> SpatialHash {
+        SpatialHash {
+            cell_size: cell_size,
+            cells: HashMap::new()
+        }
+    }
+
+    pub fn insert(&mut self, id: usize, rect: &Rect) {
+        let size = self.cell_size as i32;
+
+        for y in rect.y().div_euclid(size)..((rect.y() + rect.height() as i32 - 1).div_euclid(size) + 1) {
+            for x in rect.x().div_euclid(size)..((rect.x() + rect.width() as i32 - 1).div_euclid(size) + 1) {
+                self.cells.entry((x, y)).or_default().push(id);
+            }
+        }
+    }
+
+    // Every pair of ids that share a cell, once, with the lower id first.
+    pub fn pairs(&self) -> Vec<(usize, usize)> {
+        let mut pairs = HashSet::new();
+
+        for ids in self.cells.values() {
+            for (i, &a) in ids.iter().enumerate() {
+                for &b in &ids[(i + 1)..] {
+                    pairs.insert((a.min(b), a.max(b)));
+                }
+            }
+        }
+
+        let mut pairs = pairs.into_iter().collect::<Vec<_>>();
+
+        pairs.sort();
+        pairs
+    }
+}
+
+
+// All overlapping pairs of `bodies` whose colliders interact.
+pub fn find_contacts<T: Copy>(bodies: &[(T, Rect, Collider)], cell_size: u32) -> Vec<Contact<T>> {
+    let mut hash = SpatialHash::new(cell_size);
+
+    for (id, (_, rect, _)) in bodies.iter().enumerate() {
+        hash.insert(id, rect);
+    }
+
+    hash.pairs().into_iter().filter_map(|(i, j)| {
+        let (a, ref rect_a, ref collider_a) = bodies[i];
+        let (b, ref rect_b, ref collider_b) = bodies[j];
+
+        if !collider_a.interacts(collider_b) {
+            return None;
+        }
+
+        find_side(rect_a, rect_b).map(|side| Contact { a: a, b: b, side: side })
+    }).collect()
+}
+
+// Which side of `b` that `a` overlaps, judged by the axis where they overlap
+// the least.
+pub fn find_side(a: &Rect, b: &Rect) -> Option<Side> {
+    let overlap_x = (a.right().min(b.right()) - a.left().max(b.left())) as f32;
+    let overlap_y = (a.bottom().min(b.bottom()) - a.top().max(b.top())) as f32;
+
+    if overlap_x <= 0.0 || overlap_y <= 0.0 {
+        return None;
+    }
+
+    let dx = (a.x() * 2 + a.width() as i32) - (b.x() * 2 + b.width() as i32);
+    let dy = (a.y() * 2 + a.height() as i32) - (b.y() * 2 + b.height() as i32);
+
+    if overlap_y < overlap_x {
+        Some(if dy < 0 { Side::Top } else { Side::Bottom })
+    } else {
+        Some(if dx < 0 { Side::Left } else { Side::Right })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::rect::Rect;
+
+    use super::*;
+
+    #[test]
+    fn spatial_hash_pairs() {
+        let mut hash = SpatialHash::new(10);
+
+        hash.insert(0, &Rect::new(0, 0, 15, 5));
+        hash.insert(1, &Rect::new(12, 0, 5, 5));
+        hash.insert(2, &Rect::new(-5, -5, 3, 3));
+        hash.insert(3, &Rect::new(-10, -10, 12, 12));
+
+        assert_eq!(hash.pairs(), vec![(0, 1), (0, 3), (2, 3)]);
+    }
+
+    #[test]
+    fn contacts_sides_and_masks() {
+        let player = Collider::new(PLAYER, ENEMY | PICKUP);
+        let enemy = Collider::new(ENEMY, PLAYER | ENEMY);
+        let coin = Collider::new(PICKUP, PLAYER);
+
+        let bodies = vec![
+            ("player", Rect::new(0, 0, 32, 32), player),
+            ("enemy", Rect::new(4, 28, 32, 32), enemy),
+            ("coin", Rect::new(20, 10, 16, 16), coin),
+            ("other coin", Rect::new(34, 10, 16, 16), coin)
+        ];
+
+        assert_eq!(find_contacts(&bodies, 32), vec![
+            Contact { a: "player", b: "enemy", side: Side::Top },
+            Contact { a: "player", b: "coin", side: Side::Left }
+        ]);
+
+        assert_eq!(find_side(&Rect::new(20, 0, 10, 10), &Rect::new(0, 0, 25, 10)), Some(Side::Right));
+        assert_eq!(find_side(&Rect::new(0, 8, 10, 10), &Rect::new(0, 0, 10, 10)), Some(Side::Bottom));
+        assert_eq!(find_side(&Rect::new(10, 0, 10, 10), &Rect::new(0, 0, 10, 10)), None);
+    }
+}
@@ -0,0 +1,564 @@
+use sdl2::rect::Rect;
+use sdl2::render::{Renderer, Texture};
+
+use sprite::{Sprite, StaticSprite, AnimatedSprite};
+use game_object::{GameObject, Pose};
+use component::{GRAVITY, Updatable, Renderable, Context, Event, Touch, Transform, Velocity, Actor, Look};
+use contact::{self, Collider, Side};
+use collision::{self, Shape};
+use map::Map;
+
+
+const WALKER_SPEED : f32 = 1.0;
+const SHELL_SPEED : f32 = 6.0;
+const KNOCK_OUT_SPEED : f32 = 4.0;
+
+// how long walkers stay squashed.
+const SQUASH_TIME : f64 = 500.0;
+
+// how long a shell lies still before it starts to wake up, and how long it
+// takes to wake up.
+const SHELL_TIME : f64 = 5000.0;
+const WAKING_TIME : f64 = 2000.0;
+
+
+pub fn walker_collider() -> Collider {
+    Collider::new(contact::ENEMY, contact::PLAYER | contact::ENEMY | contact::PROJECTILE | contact::KICKABLE | contact::BLOCK)
+}
+
+
+pub fn walker<'a>(x: f32, y: f32, turn_at_ledges: bool, graphics: Box<dyn Renderable + 'a>) -> GameObject<'a> {
+    GameObject::new(x, y)
+        .with_velocity(Velocity::new(-WALKER_SPEED, 0.0))
+        .with_collider(walker_collider())
+        .with_graphics(graphics)
+        .with_ai(Box::new(WalkerPhysicsComponent::new(turn_at_ledges)))
+}
+
+pub fn koopa<'a>(x: f32, y: f32, turn_at_ledges: bool, graphics: Box<dyn Renderable + 'a>) -> GameObject<'a> {
+    GameObject::new(x, y)
+        .with_velocity(Velocity::new(-WALKER_SPEED, 0.0))
+        .with_collider(walker_collider())
+        .with_graphics(graphics)
+        .with_ai(Box::new(ShellPhysicsComponent::new(turn_at_ledges)))
+}
+
+
+// Walks left at a constant speed, turning around at walls and other enemies,
+// and at ledges if `turn_at_ledges` is set.
+pub struct WalkerPhysicsComponent {
+    dx: f32,
+    turn_at_ledges: bool,
+    squash_time: f64
+}
+
+impl WalkerPhysicsComponent {
+    pub fn new(turn_at_ledges: bool) -> WalkerPhysicsComponent {
+        WalkerPhysicsComponent {
+            dx: -WALKER_SPEED,
+            turn_at_ledges: turn_at_ledges,
+            squash_time: 0.0
+        }
+    }
+
+    // Whether there is nothing to stand on just ahead.
+    fn at_ledge(&self, transform: &Transform, map: &Map) -> bool {
+        let x = if self.dx < 0.0 { transform.x - 1.0 } else { transform.x + transform.w as f32 };
+        let y = transform.y + transform.h as f32;
+
+        map.shape((x / map.tile_width() as f32).floor() as i32, (y / map.tile_height() as f32).floor() as i32) == Shape::Empty
+    }
+}
+
+impl Updatable for WalkerPhysicsComponent {
+    fn update(&mut self, actor: &mut Actor, context: &Context) -> Vec<Event> {
+        match *actor.pose {
+            Pose::Squashed => {
+                self.squash_time -= context.dt;
+
+                *actor.removed = self.squash_time <= 0.0;
+
+                return Vec::new();
+            },
+            Pose::KnockedOut => return Vec::new(),
+            _ => ()
+        }
+
+        if actor.velocity.blocked.left || actor.velocity.blocked.right {
+            self.dx = -self.dx;
+        }
+
+        if self.turn_at_ledges && actor.velocity.on_ground && self.at_ledge(actor.transform, context.map) {
+            self.dx = -self.dx;
+        }
+
+        actor.velocity.dx = self.dx;
+        actor.transform.facing = self.dx.signum();
+
+        Vec::new()
+    }
+
+    // Is squashed when the player lands on it, knocked out by projectiles and
+    // bumped blocks, and turns away from other enemies.
+    fn touch(&mut self, actor: &mut Actor, touch: &Touch, _: &Context) -> Vec<Event> {
+        if touch.layer & (contact::PROJECTILE | contact::BLOCK) != 0 {
+            knock_out(actor, touch);
+
+            return vec![Event::Killed];
+        }
+
+        if touch.layer & contact::PLAYER != 0 && touch.side == Side::Bottom {
+            *actor.pose = Pose::Squashed;
+            *actor.collider = None;
+            *actor.velocity = Velocity::still();
+
+            self.squash_time = SQUASH_TIME;
+
+            return vec![Event::Stomped];
+        }
+
+        if touch.layer & (contact::ENEMY | contact::KICKABLE) != 0 {
+            match touch.side {
+                Side::Left => self.dx = -self.dx.abs(),
+                Side::Right => self.dx = self.dx.abs(),
+                _ => ()
+            }
+        }
+
+        Vec::new()
+    }
+}
+
+
+pub struct WalkerGraphicsComponent<'a> {
+    sprite_walking: AnimatedSprite<'a>,
+    sprite_squashed: StaticSprite<'a>
+}
+
+impl<'a> WalkerGraphicsComponent<'a> {
+    pub fn new(texture: &'a Texture) -> WalkerGraphicsComponent<'a> {
+        WalkerGraphicsComponent {
+            sprite_walking: AnimatedSprite::new(texture, 0, 0, 2, 5.0),
+            sprite_squashed: StaticSprite::new(texture, 32, 0)
+        }
+    }
+}
+
+impl<'a> Renderable for WalkerGraphicsComponent<'a> {
+    fn render(&mut self, look: &Look, elapsed: f64, renderer: &mut Renderer, destination: &Rect) {
+        if look.pose == Pose::Squashed {
+            self.sprite_squashed.render(elapsed, renderer, destination);
+        } else {
+            self.sprite_walking.flip_vertical = look.pose == Pose::KnockedOut;
+            self.sprite_walking.render(elapsed, renderer, destination);
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum ShellState {
+    Walking,
+    Shell,
+    Sliding,
+    Carried(f32)
+}
+
+// Walks like a walker until it is stomped, then hides in its shell, which the
+// player can kick or carry while holding the action button. A sliding shell
+// knocks out the enemies it hits, and is stopped by stomping on it again.
+pub struct ShellPhysicsComponent {
+    walker: WalkerPhysicsComponent,
+    state: ShellState,
+    dx: f32,
+    time: f64
+}
+
+impl ShellPhysicsComponent {
+    pub fn new(turn_at_ledges: bool) -> ShellPhysicsComponent {
+        ShellPhysicsComponent {
+            walker: WalkerPhysicsComponent::new(turn_at_ledges),
+            state: ShellState::Walking,
+            dx: 0.0,
+            time: 0.0
+        }
+    }
+
+    fn enter(&mut self, actor: &mut Actor, state: ShellState) {
+        let (pose, collider) = match state {
+            ShellState::Walking => (Pose::Normal, walker_collider()),
+            ShellState::Shell => (Pose::Shell, Collider::new(contact::KICKABLE, contact::PLAYER | contact::PROJECTILE | contact::BLOCK)),
+            ShellState::Sliding => (Pose::Shell, Collider::new(contact::ENEMY | contact::PROJECTILE, contact::PLAYER | contact::ENEMY | contact::KICKABLE | contact::BLOCK)),
+            ShellState::Carried(_) => (Pose::Shell, Collider::new(contact::PROJECTILE, contact::ENEMY | contact::KICKABLE))
+        };
+
+        match state {
+            ShellState::Shell => {
+                self.time = 0.0;
+                self.dx = 0.0;
+            },
+            ShellState::Carried(_) => {
+                actor.velocity.dx = 0.0;
+                actor.velocity.dy = 0.0;
+            },
+            _ => ()
+        }
+
+        // the player holds a carried shell up.
+        actor.velocity.gravity = if let ShellState::Carried(_) = state { 0.0 } else { GRAVITY };
+
+        self.state = state;
+
+        *actor.pose = pose;
+        *actor.collider = Some(collider);
+    }
+
+    // Kicks the shell away from the player, from just outside of it so that
+    // the player is not hurt by it right away, or from the wall in between.
+    fn kick(&mut self, actor: &mut Actor, map: &Map, player: &Rect, direction: f32) {
+        let x = if direction < 0.0 { player.left() as f32 - actor.transform.w as f32 } else { player.right() as f32 };
+        let y = actor.transform.y;
+
+        place(actor.transform, map, x, y);
+
+        self.dx = direction * SHELL_SPEED;
+
+        actor.velocity.dx = self.dx;
+
+        self.enter(actor, ShellState::Sliding);
+    }
+
+    // Counts down to waking up, in or out of the player's hands.
+    fn rest(&mut self, actor: &mut Actor, context: &Context) {
+        self.time += context.dt;
+
+        if self.time >= SHELL_TIME + WAKING_TIME {
+            self.enter(actor, ShellState::Walking);
+        } else if self.time >= SHELL_TIME {
+            *actor.pose = Pose::ShellWaking;
+        }
+    }
+}
+
+impl Updatable for ShellPhysicsComponent {
+    fn update(&mut self, actor: &mut Actor, context: &Context) -> Vec<Event> {
+        if *actor.pose == Pose::KnockedOut {
+            return Vec::new();
+        }
+
+        match self.state {
+            ShellState::Walking => return self.walker.update(actor, context),
+            ShellState::Shell => {
+                actor.velocity.dx = 0.0;
+
+                self.rest(actor, context);
+            },
+            ShellState::Sliding => {
+                if actor.velocity.blocked.left || actor.velocity.blocked.right {
+                    self.dx = -self.dx;
+                }
+
+                actor.velocity.dx = self.dx;
+                actor.transform.facing = self.dx.signum();
+            },
+            ShellState::Carried(side) => {
+                let player = match context.player {
+                    Some(player) => player,
+                    None => return Vec::new()
+                };
+
+                let x = if side < 0.0 { player.left() as f32 - actor.transform.w as f32 / 2.0 } else { player.right() as f32 - actor.transform.w as f32 / 2.0 };
+
+                place(actor.transform, context.map, x, player.y() as f32);
+
+                if !context.input.action {
+                    self.kick(actor, context.map, &player, side);
+
+                    return vec![Event::Kicked];
+                }
+
+                self.rest(actor, context);
+            }
+        }
+
+        Vec::new()
+    }
+
+    fn touch(&mut self, actor: &mut Actor, touch: &Touch, context: &Context) -> Vec<Event> {
+        if touch.layer & (contact::PROJECTILE | contact::BLOCK) != 0 {
+            knock_out(actor, touch);
+
+            return vec![Event::Killed];
+        }
+
+        let player = touch.layer & contact::PLAYER != 0;
+
+        match self.state {
+            ShellState::Walking if player && touch.side == Side::Bottom => {
+                self.enter(actor, ShellState::Shell);
+
+                vec![Event::Stomped]
+            },
+            ShellState::Walking => self.walker.touch(actor, touch, context),
+            ShellState::Shell if player => {
+                let direction = if center(&touch.rect).0 < center(&actor.transform.to_rect()).0 { 1.0 } else { -1.0 };
+
+                if context.input.action {
+                    self.enter(actor, ShellState::Carried(direction));
+
+                    Vec::new()
+                } else {
+                    self.kick(actor, context.map, &touch.rect, direction);
+
+                    vec![Event::Kicked]
+                }
+            },
+            ShellState::Sliding if player && touch.side == Side::Bottom => {
+                self.enter(actor, ShellState::Shell);
+
+                vec![Event::Stomped]
+            },
+            _ => Vec::new()
+        }
+    }
+}
+
+
+pub struct ShellGraphicsComponent<'a> {
+    sprite_walking: AnimatedSprite<'a>,
+    sprite_shell: StaticSprite<'a>,
+    sprite_waking: AnimatedSprite<'a>
+}
+
+impl<'a> ShellGraphicsComponent<'a> {
+    pub fn new(texture: &'a Texture) -> ShellGraphicsComponent<'a> {
+        ShellGraphicsComponent {
+            sprite_walking: AnimatedSprite::new(texture, 48, 0, 2, 5.0),
+            sprite_shell: StaticSprite::new(texture, 80, 0),
+            sprite_waking: AnimatedSprite::new(texture, 80, 0, 2, 8.0)
+        }
+    }
+}
+
+impl<'a> Renderable for ShellGraphicsComponent<'a> {
+    fn render(&mut self, look: &Look, elapsed: f64, renderer: &mut Renderer, destination: &Rect) {
+        match look.pose {
+            Pose::Shell | Pose::KnockedOut => {
+                self.sprite_shell.flip_vertical = look.pose == Pose::KnockedOut;
+                self.sprite_shell.render(elapsed, renderer, destination);
+            },
+            Pose::ShellWaking => self.sprite_waking.render(elapsed, renderer, destination),
+            _ => {
+                // the sprite faces left.
+                self.sprite_walking.flip_horizontal = look.facing > 0.0;
+                self.sprite_walking.render(elapsed, renderer, destination);
+            }
+        }
+    }
+}
+
+
+pub fn center(rect: &Rect) -> (f32, f32) {
+    (rect.x() as f32 + rect.width() as f32 / 2.0, rect.y() as f32 + rect.height() as f32 / 2.0)
+}
+
+// Moves the object towards `x`, `y`, stopping at the map on the way.
+fn place(transform: &mut Transform, map: &Map, x: f32, y: f32) {
+    let result = collision::move_and_collide(map, &transform.to_body(), x - transform.x, y - transform.y, false);
+
+    transform.x = result.x;
+    transform.y = result.y;
+}
+
+// Knocks the object out, to fly away from `touch` and fall through
+// everything.
+pub fn knock_out(actor: &mut Actor, touch: &Touch) {
+    *actor.pose = Pose::KnockedOut;
+    *actor.collider = None;
+
+    let dx = if center(&touch.rect).0 < center(&actor.transform.to_rect()).0 { WALKER_SPEED } else { -WALKER_SPEED };
+
+    *actor.velocity = Velocity { solid: false, ..Velocity::new(dx, -KNOCK_OUT_SPEED) };
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::rect::Rect;
+
+    use component::{TICK, Input};
+    use world::World;
+    use test_util::{self, Invisible};
+
+    use super::*;
+
+    fn map() -> Map {
+        test_util::map(&[
+            "........",
+            "........",
+            "#......#",
+            "#####..#"
+        ])
+    }
+
+    #[test]
+    fn walker_patrol() {
+        let map = map();
+
+        let context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+        let mut world = World::new(32);
+        let goomba = world.spawn(walker(64.0, 64.0, false, Box::new(Invisible)));
+
+        // walks left from the start, whether or not there is a player.
+        for _ in 0..10 {
+            world.update(&context);
+        }
+
+        assert_eq!((world.transform(goomba).unwrap().x, world.velocity(goomba).unwrap().dx), (54.0, -WALKER_SPEED));
+
+        // turns around at the wall, and falls into the hole.
+        for _ in 0..200 {
+            world.update(&context);
+        }
+
+        assert!(world.transform(goomba).is_none_or(|transform| transform.y > 96.0));
+
+        // or turns around at the ledge.
+        let goomba = world.spawn(walker(64.0, 64.0, true, Box::new(Invisible)));
+
+        for _ in 0..400 {
+            world.update(&context);
+
+            let x = world.transform(goomba).unwrap().x;
+
+            assert!((32.0..=160.0).contains(&x));
+        }
+    }
+
+    #[test]
+    fn walker_stomp() {
+        let map = map();
+
+        let context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+        let mut world = World::new(32);
+        let goomba = world.spawn(walker(64.0, 64.0, false, Box::new(Invisible)));
+
+        world.maintain();
+
+        let touch = Touch { entity: None, side: Side::Left, layer: contact::PLAYER, rect: Rect::new(32, 64, 32, 32) };
+
+        assert!(world.touch(goomba, &touch, &context).is_empty());
+        assert_eq!(world.pose(goomba), Some(Pose::Normal));
+
+        assert_eq!(world.touch(goomba, &Touch { side: Side::Bottom, ..touch }, &context), vec![(goomba, Event::Stomped)]);
+        assert_eq!((world.pose(goomba), world.collider(goomba)), (Some(Pose::Squashed), None));
+
+        for _ in 0..50 {
+            world.update(&context);
+        }
+
+        assert!(!world.exists(goomba));
+    }
+
+    #[test]
+    fn shell_states() {
+        let map = map();
+
+        let mut context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+        let mut world = World::new(32);
+        let shell = world.spawn(koopa(64.0, 64.0, false, Box::new(Invisible)));
+
+        world.maintain();
+
+        let stomp = Touch { entity: None, side: Side::Bottom, layer: contact::PLAYER, rect: Rect::new(64, 36, 32, 32) };
+
+        assert_eq!(world.touch(shell, &stomp, &context), vec![(shell, Event::Stomped)]);
+        assert_eq!((world.pose(shell), world.collider(shell).unwrap().layer), (Some(Pose::Shell), contact::KICKABLE));
+
+        // wakes up after lying still for a while.
+        for _ in 0..((SHELL_TIME / TICK) as usize) {
+            world.update(&context);
+        }
+
+        assert_eq!(world.pose(shell), Some(Pose::ShellWaking));
+
+        for _ in 0..((WAKING_TIME / TICK) as usize) {
+            world.update(&context);
+        }
+
+        assert_eq!(world.pose(shell), Some(Pose::Normal));
+
+        // is kicked away from the player, and knocks out other enemies.
+        world.touch(shell, &stomp, &context);
+
+        let kick = Touch { side: Side::Left, rect: Rect::new(40, 64, 32, 32), ..stomp };
+
+        assert_eq!(world.touch(shell, &kick, &context), vec![(shell, Event::Kicked)]);
+        assert_eq!((world.transform(shell).unwrap().x, world.velocity(shell).unwrap().dx), (72.0, SHELL_SPEED));
+        assert_eq!(world.collider(shell).unwrap().layer, contact::ENEMY | contact::PROJECTILE);
+
+        let goomba = world.spawn(walker(64.0, 64.0, false, Box::new(Invisible)));
+
+        world.maintain();
+
+        let hit = Touch { side: Side::Right, layer: world.collider(shell).unwrap().layer, rect: world.transform(shell).unwrap().to_rect(), ..stomp };
+
+        assert_eq!(world.touch(goomba, &hit, &context), vec![(goomba, Event::Killed)]);
+        assert_eq!(world.pose(goomba), Some(Pose::KnockedOut));
+        assert_eq!(world.velocity(goomba).map(|velocity| (velocity.dx, velocity.dy)), Some((-WALKER_SPEED, -KNOCK_OUT_SPEED)));
+
+        // is stopped by stomping on it, and carried while holding the button.
+        assert_eq!(world.touch(shell, &stomp, &context), vec![(shell, Event::Stomped)]);
+
+        context.input.action = true;
+        context.player = Some(kick.rect);
+
+        assert!(world.touch(shell, &kick, &context).is_empty());
+
+        world.update(&context);
+
+        assert_eq!(world.transform(shell).map(|transform| (transform.x, transform.y)), Some((56.0, 64.0)));
+
+        // is kicked from beside the player, and slides on from there.
+        context.input.action = false;
+
+        assert!(world.update(&context).contains(&(shell, Event::Kicked)));
+        assert_eq!((world.transform(shell).unwrap().x, world.velocity(shell).unwrap().dx), (72.0 + SHELL_SPEED, SHELL_SPEED));
+    }
+
+    #[test]
+    fn shell_against_wall() {
+        let map = map();
+
+        let mut context = Context { map: &map, input: Input { action: true, ..Input::default() }, player: None, dt: TICK };
+        let mut world = World::new(32);
+        let shell = world.spawn(koopa(64.0, 64.0, false, Box::new(Invisible)));
+
+        world.maintain();
+
+        let stomp = Touch { entity: None, side: Side::Bottom, layer: contact::PLAYER, rect: Rect::new(64, 36, 32, 32) };
+
+        world.touch(shell, &stomp, &context);
+        world.touch(shell, &Touch { side: Side::Right, rect: Rect::new(88, 64, 32, 32), ..stomp }, &context);
+
+        // is held against the wall rather than pushed into it, and is kicked
+        // from there when it is let go.
+        context.player = Some(Rect::new(32, 64, 32, 32));
+
+        world.update(&context);
+
+        assert_eq!(world.transform(shell).map(|transform| (transform.x, transform.y)), Some((32.0, 64.0)));
+
+        context.input.action = false;
+
+        assert!(world.update(&context).contains(&(shell, Event::Kicked)));
+        assert_eq!(world.transform(shell).unwrap().x, 32.0);
+
+        for _ in 0..10 {
+            world.update(&context);
+
+            assert!(world.transform(shell).unwrap().x >= 32.0);
+        }
+
+        assert_eq!(world.velocity(shell).unwrap().dx, SHELL_SPEED);
+    }
+}
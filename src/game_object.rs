@@ -1,46 +1,117 @@
 use sdl2::rect::Rect;
 use sdl2::render::Renderer;
 
-use component::{Updatable, Renderable};
+use component::{Updatable, Renderable, Transform, Velocity, Health, Look};
+use contact::Collider;
+use item_components::Pickup;
 
+// What an object is doing, for its graphics to show.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Pose {
+    Normal,
+    Squashed,
+    KnockedOut,
+    Shell,
+    ShellWaking,
+    Growing,
+    Shrinking,
+    Blinking,
+    Shining,
+    Dying,
+    Sliding,
+    Entering,
+    Puff
+}
+
+
+// How powered up an object is, only the player ever is.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Power {
+    Small,
+    Big,
+    Fire
+}
+
+
+// The pose of an object and the graphics that draw it.
+pub struct Animation<'a> {
+    pub pose: Pose,
+    graphics: Box<dyn Renderable + 'a>
+}
+
+impl<'a> Animation<'a> {
+    pub fn new(graphics: Box<dyn Renderable + 'a>) -> Animation<'a> {
+        Animation {
+            pose: Pose::Normal,
+            graphics: graphics
+        }
+    }
+
+    pub fn render(&mut self, look: &Look, elapsed: f64, renderer: &mut Renderer, destination: &Rect) {
+        self.graphics.render(look, elapsed, renderer, destination);
+    }
+}
+
+
+// The components that an entity is spawned with. Every entity has a
+// transform, the rest are optional, but only entities with a velocity and
+// an animation are run by their AI.
 pub struct GameObject<'a> {
-    pub x: f32,
-    pub y: f32,
-    pub w: u32,
-    pub h: u32,
-    pub dx: f32, // TODO moved to PlayerPhysicsComponent
-    pub dy: f32, // TODO moved to PlayerPhysicsComponent
-    pub gravity: f32, // TODO moved to PlayerPhysicsComponent
-    pub on_ground: bool,
-    physics: Box<Updatable + 'a>,
-    graphics: Box<Renderable + 'a>
+    pub transform: Transform,
+    pub velocity: Option<Velocity>,
+    pub collider: Option<Collider>,
+    pub animation: Option<Animation<'a>>,
+    pub ai: Option<Box<dyn Updatable + 'a>>,
+    pub health: Option<Health>,
+    pub pickup: Option<Pickup>
 }
 
 impl<'a> GameObject<'a> {
-    pub fn new(x: f32, y: f32, physics: Box<Updatable + 'a>, graphics: Box<Renderable + 'a>) -> GameObject<'a> {
+    pub fn new(x: f32, y: f32) -> GameObject<'a> {
         GameObject {
-            x: x,
-            y: y,
-            w: 32,
-            h: 32,
-            dx: 0.0,
-            dy: 0.0,
-            gravity: 0.3,
-            on_ground: false,
-            physics: physics,
-            graphics: graphics
+            transform: Transform::new(x, y, 32, 32),
+            velocity: None,
+            collider: None,
+            animation: None,
+            ai: None,
+            health: None,
+            pickup: None
         }
     }
 
-    pub fn update(&self) {
-        self.physics.update(self);
+    pub fn with_size(mut self, w: u32, h: u32) -> GameObject<'a> {
+        self.transform.w = w;
+        self.transform.h = h;
+        self
+    }
+
+    pub fn with_velocity(mut self, velocity: Velocity) -> GameObject<'a> {
+        self.velocity = Some(velocity);
+        self
+    }
+
+    pub fn with_collider(mut self, collider: Collider) -> GameObject<'a> {
+        self.collider = Some(collider);
+        self
+    }
+
+    pub fn with_graphics(mut self, graphics: Box<dyn Renderable + 'a>) -> GameObject<'a> {
+        self.animation = Some(Animation::new(graphics));
+        self
+    }
+
+    pub fn with_ai(mut self, ai: Box<dyn Updatable + 'a>) -> GameObject<'a> {
+        self.ai = Some(ai);
+        self
     }
 
-    pub fn render(&self, elapsed: f64, renderer: &mut Renderer, destination: &Rect) {
-        self.graphics.render(self, elapsed, renderer, destination);
+    pub fn with_health(mut self, health: Health) -> GameObject<'a> {
+        self.health = Some(health);
+        self
     }
 
-    pub fn to_rect(&self) -> Rect {
-        Rect::new(self.x as i32, self.y as i32, 32, 32)
+    pub fn with_pickup(mut self, pickup: Pickup) -> GameObject<'a> {
+        self.pickup = Some(pickup);
+        self
     }
 }
@@ -0,0 +1,149 @@
+use sdl2::rect::Rect;
+use sdl2::render::{Renderer, Texture};
+
+use sprite::{Sprite, StaticSprite};
+use game_object::{GameObject, Pose};
+use component::{TICK, Updatable, Renderable, Context, Event, Velocity, Actor, Look};
+use collision::Shape;
+use map::{Map, Object};
+
+
+// how fast the player and the flag slide down the pole.
+pub const SLIDE_SPEED : f32 = 4.0;
+
+
+// The flagpole at the end of a level, from the `goal` object at its top down
+// to the first solid tile below it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Goal {
+    pub x: f32,
+    pub top: f32,
+    pub bottom: f32
+}
+
+impl Goal {
+    pub fn new(map: &Map, object: &Object) -> Goal {
+        let column = (object.x / map.tile_width() as f32) as i32;
+        let mut row = (object.y / map.tile_height() as f32) as i32;
+        let last = map.to_rect().bottom() / map.tile_height() as i32;
+
+        while row < last && map.shape(column, row) != Shape::Solid {
+            row += 1;
+        }
+
+        Goal {
+            x: object.x + (map.tile_width() / 2) as f32,
+            top: object.y,
+            bottom: (row * map.tile_height() as i32) as f32
+        }
+    }
+
+    // The pole, which the player grabs by touching it.
+    pub fn to_rect(self) -> Rect {
+        Rect::new(self.x as i32 - 2, self.top as i32, 4, ((self.bottom - self.top) as u32).max(1))
+    }
+}
+
+
+// The flag that hangs at the top of the pole of `goal`.
+pub fn flag<'a>(goal: &Goal, size: u32, texture: &'a Texture) -> GameObject<'a> {
+    GameObject::new(goal.x - size as f32, goal.top + size as f32)
+        .with_size(size, size)
+        .with_velocity(Velocity::still())
+        .with_graphics(Box::new(FlagGraphicsComponent::new(texture)))
+        .with_ai(Box::new(FlagPhysicsComponent::new(goal.bottom - size as f32)))
+}
+
+
+// Slides down to `bottom` once the game sets it sliding.
+pub struct FlagPhysicsComponent {
+    bottom: f32
+}
+
+impl FlagPhysicsComponent {
+    pub fn new(bottom: f32) -> FlagPhysicsComponent {
+        FlagPhysicsComponent {
+            bottom: bottom
+        }
+    }
+}
+
+impl Updatable for FlagPhysicsComponent {
+    fn update(&mut self, actor: &mut Actor, context: &Context) -> Vec<Event> {
+        if *actor.pose == Pose::Sliding {
+            actor.velocity.dy = ((self.bottom - actor.transform.y) / (context.dt / TICK) as f32).min(SLIDE_SPEED);
+        }
+
+        Vec::new()
+    }
+}
+
+
+pub struct FlagGraphicsComponent<'a> {
+    sprite: StaticSprite<'a>
+}
+
+impl<'a> FlagGraphicsComponent<'a> {
+    pub fn new(texture: &'a Texture) -> FlagGraphicsComponent<'a> {
+        FlagGraphicsComponent {
+            sprite: StaticSprite::new(texture, 176, 0)
+        }
+    }
+}
+
+impl<'a> Renderable for FlagGraphicsComponent<'a> {
+    fn render(&mut self, _: &Look, elapsed: f64, renderer: &mut Renderer, destination: &Rect) {
+        self.sprite.render(elapsed, renderer, destination);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use component::Input;
+    use world::World;
+    use test_util::{self, Invisible};
+
+    use super::*;
+
+    #[test]
+    fn goal_pole() {
+        // a block at the foot of a pole that starts at the top of the map.
+        let map = test_util::map(&[
+            "....",
+            "....",
+            "....",
+            "....",
+            "....",
+            "....",
+            "....",
+            "....",
+            "..#.",
+            "...."
+        ]);
+
+        let object = Object { name: String::new(), kind: "goal".to_string(), x: 64.0, y: 32.0, width: 32.0, height: 32.0, properties: HashMap::new() };
+        let goal = Goal::new(&map, &object);
+
+        assert_eq!(goal, Goal { x: 80.0, top: 32.0, bottom: 256.0 });
+        assert_eq!(goal.to_rect(), Rect::new(78, 32, 4, 224));
+
+        // the flag slides down to the block once it is set sliding.
+        let mut world = World::new(32);
+        let flag = world.spawn(GameObject::new(48.0, 64.0).with_velocity(Velocity::still()).with_graphics(Box::new(Invisible)).with_ai(Box::new(FlagPhysicsComponent::new(goal.bottom - 32.0))));
+        let context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+
+        world.update(&context);
+
+        assert_eq!(world.transform(flag).unwrap().y, 64.0);
+
+        world.set_pose(flag, Pose::Sliding);
+
+        for _ in 0..100 {
+            world.update(&context);
+        }
+
+        assert_eq!(world.transform(flag).unwrap().y, 224.0);
+    }
+}
@@ -0,0 +1,254 @@
+use sdl2::rect::Rect;
+use sdl2::render::{Renderer, Texture};
+
+use sprite::{Sprite, AnimatedSprite};
+use game_object::GameObject;
+use component::{TICK, Updatable, Renderable, Context, Event, Touch, Velocity, Actor, Look};
+use contact::{self, Collider};
+use enemy_components;
+
+
+const RISE_SPEED : f32 = 0.5;
+const WALK_SPEED : f32 = 2.0;
+const HOP_SPEED : f32 = 6.0;
+const COIN_SPEED : f32 = 10.0;
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Item {
+    Coin,
+    Mushroom,
+    Flower,
+    Star,
+    OneUp
+}
+
+impl Item {
+    pub fn from_name(name: &str) -> Option<Item> {
+        match name {
+            "coin" => Some(Item::Coin),
+            "mushroom" => Some(Item::Mushroom),
+            "flower" => Some(Item::Flower),
+            "star" => Some(Item::Star),
+            "1-up" => Some(Item::OneUp),
+            _ => None
+        }
+    }
+}
+
+
+pub fn item_collider() -> Collider {
+    Collider::new(contact::PICKUP, contact::PLAYER | contact::BLOCK)
+}
+
+
+// What touching an object gives, and who can take it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Pickup {
+    pub item: Item,
+    pub mask: u32
+}
+
+impl Pickup {
+    // Items are collected by the player, and coins also by the blocks bumped
+    // below them.
+    pub fn new(item: Item) -> Pickup {
+        Pickup {
+            item: item,
+            mask: if item == Item::Coin { contact::PLAYER | contact::BLOCK } else { contact::PLAYER }
+        }
+    }
+}
+
+
+// An item lying in the level.
+pub fn item<'a>(x: f32, y: f32, item: Item, graphics: Box<dyn Renderable + 'a>) -> GameObject<'a> {
+    let ai = ItemPhysicsComponent::new(item);
+
+    GameObject::new(x, y)
+        .with_velocity(ai.moving())
+        .with_collider(item_collider())
+        .with_graphics(graphics)
+        .with_pickup(Pickup::new(item))
+        .with_ai(Box::new(ai))
+}
+
+// An item coming out of a block, at `x`, `y` in it or just above it for
+// coins. Coins pop out of the top and are collected on the way, the others
+// rise out of it first.
+pub fn from_block<'a>(x: f32, y: f32, item: Item, height: u32, graphics: Box<dyn Renderable + 'a>) -> GameObject<'a> {
+    let mut ai = ItemPhysicsComponent::new(item);
+
+    if item == Item::Coin {
+        ai.popped = true;
+
+        return GameObject::new(x, y)
+            .with_velocity(Velocity { solid: false, terminal: COIN_SPEED, ..Velocity::new(0.0, -COIN_SPEED) })
+            .with_graphics(graphics)
+            .with_ai(Box::new(ai));
+    }
+
+    ai.rise = height as f32;
+
+    GameObject::new(x, y)
+        .with_velocity(Velocity::still())
+        .with_collider(item_collider())
+        .with_graphics(graphics)
+        .with_pickup(Pickup::new(item))
+        .with_ai(Box::new(ai))
+}
+
+
+// Coins and flowers stay where they are, mushrooms and 1-ups slide along the
+// ground and stars bounce.
+pub struct ItemPhysicsComponent {
+    item: Item,
+    dx: f32,
+    rise: f32,
+    popped: bool,
+    collected: bool
+}
+
+impl ItemPhysicsComponent {
+    pub fn new(item: Item) -> ItemPhysicsComponent {
+        ItemPhysicsComponent {
+            item: item,
+            dx: if item == Item::Coin || item == Item::Flower { 0.0 } else { WALK_SPEED },
+            rise: 0.0,
+            popped: false,
+            collected: false
+        }
+    }
+
+    // How the item moves once it is out of its block.
+    fn moving(&self) -> Velocity {
+        if self.dx == 0.0 { Velocity::still() } else { Velocity::new(self.dx, 0.0) }
+    }
+}
+
+impl Updatable for ItemPhysicsComponent {
+    fn update(&mut self, actor: &mut Actor, context: &Context) -> Vec<Event> {
+        let scale = (context.dt / TICK) as f32;
+
+        if self.popped {
+            *actor.removed = actor.velocity.dy >= COIN_SPEED / 2.0;
+
+            if !self.collected && actor.velocity.dy >= 0.0 {
+                self.collected = true;
+
+                return vec![Event::Collected(self.item)];
+            }
+
+            return Vec::new();
+        }
+
+        if self.rise > 0.0 {
+            let step = (RISE_SPEED * scale).min(self.rise);
+
+            self.rise -= step;
+
+            actor.velocity.dy = -step / scale;
+
+            return Vec::new();
+        }
+
+        if !actor.velocity.solid {
+            *actor.velocity = self.moving();
+        }
+
+        if actor.velocity.blocked.left || actor.velocity.blocked.right {
+            self.dx = -self.dx;
+        }
+
+        if self.item == Item::Star && actor.velocity.blocked.bottom {
+            actor.velocity.dy = -HOP_SPEED;
+        }
+
+        actor.velocity.dx = self.dx;
+
+        Vec::new()
+    }
+
+    // Hops away from the block bumped below it, if it moves at all.
+    fn touch(&mut self, actor: &mut Actor, touch: &Touch, _: &Context) -> Vec<Event> {
+        if touch.layer & contact::BLOCK != 0 && self.dx != 0.0 {
+            actor.velocity.dy = -HOP_SPEED;
+
+            self.dx = if enemy_components::center(&touch.rect).0 < enemy_components::center(&actor.transform.to_rect()).0 { WALK_SPEED } else { -WALK_SPEED };
+        }
+
+        Vec::new()
+    }
+}
+
+
+pub struct ItemGraphicsComponent<'a> {
+    sprite: AnimatedSprite<'a>
+}
+
+impl<'a> ItemGraphicsComponent<'a> {
+    pub fn new(texture: &'a Texture, item: Item) -> ItemGraphicsComponent<'a> {
+        let (x, frames) = match item {
+            Item::Coin => (0, 4),
+            Item::Mushroom => (64, 1),
+            Item::OneUp => (80, 1),
+            Item::Flower => (96, 2),
+            Item::Star => (128, 2)
+        };
+
+        ItemGraphicsComponent {
+            sprite: AnimatedSprite::new(texture, x, 0, frames, 8.0)
+        }
+    }
+}
+
+impl<'a> Renderable for ItemGraphicsComponent<'a> {
+    fn render(&mut self, _: &Look, elapsed: f64, renderer: &mut Renderer, destination: &Rect) {
+        self.sprite.render(elapsed, renderer, destination);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::rect::Rect;
+
+    use component::Input;
+    use contact::Side;
+    use map::Map;
+    use world::World;
+    use test_util::Invisible;
+
+    use super::*;
+
+    #[test]
+    fn items_from_blocks() {
+        let map = Map::new(4, 4, 32, 32);
+        let context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+        let mut world = World::new(32);
+
+        // coins are collected once, at the top of their jump.
+        let coin = world.spawn(from_block(32.0, 32.0, Item::Coin, 32, Box::new(Invisible)));
+        let mut events = Vec::new();
+
+        while world.exists(coin) {
+            events.extend(world.update(&context));
+        }
+
+        assert_eq!(events, vec![(coin, Event::Collected(Item::Coin))]);
+
+        // the others rise out of the block before they move.
+        let mushroom = world.spawn(from_block(32.0, 64.0, Item::Mushroom, 32, Box::new(Invisible)));
+
+        for _ in 0..64 {
+            world.update(&context);
+        }
+
+        assert_eq!(world.transform(mushroom).map(|transform| (transform.x, transform.y)), Some((32.0, 32.0)));
+
+        // and are collected by the player, once.
+        let touch = Touch { entity: None, side: Side::Left, layer: contact::PLAYER, rect: Rect::new(0, 32, 32, 32) };
+
+        assert_eq!(world.touch(mushroom, &touch, &context), vec![(mushroom, Event::Collected(Item::Mushroom))]);
+        assert!(world.touch(mushroom, &touch, &context).is_empty());
+    }
+}
@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use map::{Map, Pass, Parallax, Image, Edges, Object};
+use registry::{Registry, TileType};
+use collision::Shape;
+
+
+#[derive(Clone)]
+pub enum Tile {
+    Empty,
+    Defined(Rc<TileType>)
+}
+
+impl Tile {
+    pub fn kind(&self) -> Option<&TileType> {
+        match *self {
+            Tile::Defined(ref kind) => Some(kind),
+            Tile::Empty => None
+        }
+    }
+
+    pub fn shape(&self) -> Shape {
+        self.kind().map(|kind| kind.shape()).unwrap_or(Shape::Empty)
+    }
+}
+
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Syntax(usize, String),
+    UnknownTile(usize, usize, char),
+    UnknownType(usize, String),
+    Dimensions(usize, String)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Syntax(line, ref message) => write!(f, "line {}: {}", line, message),
+            Error::UnknownTile(line, column, code) => write!(f, "line {}, column {}: unknown tile '{}'", line, column, code),
+            Error::UnknownType(line, ref name) => write!(f, "line {}: unknown tile type '{}'", line, name),
+            Error::Dimensions(line, ref message) => write!(f, "line {}: {}", line, message)
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+
+pub struct Loader<'r> {
+    registry: &'r Registry,
+    tile_width: u32,
+    tile_height: u32
+}
+
+impl<'r> Loader<'r> {
+    pub fn new(registry: &'r Registry, tile_width: u32, tile_height: u32) -> Loader<'r> {
+        Loader {
+            registry: registry,
+            tile_width: tile_width,
+            tile_height: tile_height
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<Map, Error> {
+        let mut source = String::new();
+
+        File::open(path)?.read_to_string(&mut source)?;
+
+        self.parse(&source)
+    }
+
+    pub fn parse(&self, source: &str) -> Result<Map, Error> {
+        let mut map = None;
+        let mut size = (0, 0);
+        let mut legend = HashMap::new();
+        let mut lines = source.lines().enumerate().map(|(i, line)| (i + 1, line)).peekable();
+
+        while let Some((number, line)) = lines.next() {
+            let line = line.trim();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let words = line.split_whitespace().collect::<Vec<_>>();
+
+            match words[0] {
+                "size" => {
+                    if words.len() != 3 {
+                        return Err(Error::Syntax(number, "expected `size <width> <height>`".to_string()));
+                    }
+
+                    if map.is_some() {
+                        return Err(Error::Syntax(number, "`size` must come before any `layer`".to_string()));
+                    }
+
+                    size = (parse_number(number, words[1])?, parse_number(number, words[2])?);
+                    map = Some(Map::new(size.0, size.1, self.tile_width, self.tile_height));
+                },
+                "edges" => {
+                    let edges = match Edges::from_names(&words[1..]) {
+                        Some(edges) => edges,
+                        None => return Err(Error::Syntax(number, "expected `edges <left> <right> <top> <bottom>` with `solid`, `empty` or `clamp`".to_string()))
+                    };
+
+                    match map {
+                        Some(ref mut map) => map.edges = edges,
+                        None => return Err(Error::Syntax(number, "`size` must come before `edges`".to_string()))
+                    }
+                },
+                "object" => {
+                    if words.len() < 4 || words.len() % 2 != 0 {
+                        return Err(Error::Syntax(number, "expected `object <kind> <column> <row> [<property> <value>]...`".to_string()));
+                    }
+
+                    let (x, y): (i32, i32) = (parse_number(number, words[2])?, parse_number(number, words[3])?);
+
+                    let properties: HashMap<String, String> = words[4..].chunks(2).map(|pair| (pair[0].to_string(), pair[1].to_string())).collect();
+
+                    // objects cover a tile, unless they are `width` columns
+                    // wide or `height` rows high.
+                    let columns: u32 = match properties.get("width") {
+                        Some(width) => parse_number(number, width)?,
+                        None => 1
+                    };
+
+                    let rows: u32 = match properties.get("height") {
+                        Some(height) => parse_number(number, height)?,
+                        None => 1
+                    };
+
+                    let object = Object {
+                        name: properties.get("name").cloned().unwrap_or_default(),
+                        kind: words[1].to_string(),
+                        x: (x * self.tile_width as i32) as f32,
+                        y: (y * self.tile_height as i32) as f32,
+                        width: (columns * self.tile_width) as f32,
+                        height: (rows * self.tile_height) as f32,
+                        properties: properties
+                    };
+
+                    match map {
+                        Some(ref mut map) => map.objects.push(object),
+                        None => return Err(Error::Syntax(number, "`size` must come before `object`".to_string()))
+                    }
+                },
+                "legend" => {
+                    for (number, line) in lines.by_ref() {
+                        let line = line.trim();
+
+                        if line.is_empty() {
+                            break;
+                        }
+
+                        let (code, tile) = self.parse_legend(number, line)?;
+
+                        legend.insert(code, tile);
+                    }
+                },
+                "layer" => {
+                    if words.len() < 3 {
+                        return Err(Error::Syntax(number, "expected `layer <name> <background|collision|foreground> [options]`".to_string()));
+                    }
+
+                    let pass = parse_pass(number, words[2])?;
+                    let options = parse_options(number, &words[3..])?;
+
+                    let width = options.width.unwrap_or(size.0);
+                    let height = size.1;
+
+                    if pass == Pass::Collision && (width != size.0 || !options.parallax.is_none()) {
+                        return Err(Error::Syntax(number, "collision layers must cover the level and can not scroll".to_string()));
+                    }
+
+                    let layer = match map {
+                        Some(ref mut map) => map.add_layer(pass, width, options.parallax),
+                        None => return Err(Error::Syntax(number, "`size` must come before `layer`".to_string()))
+                    };
+
+                    for y in 0..height {
+                        let (number, row) = match lines.next() {
+                            Some(line) => line,
+                            None => return Err(Error::Dimensions(number + y as usize, format!("expected {} rows, found {}", height, y)))
+                        };
+
+                        let count = row.chars().count() as u32;
+
+                        if count != width {
+                            return Err(Error::Dimensions(number, format!("expected {} columns, found {}", width, count)));
+                        }
+
+                        for (x, code) in row.chars().enumerate() {
+                            match legend.get(&code) {
+                                Some(&Tile::Empty) => (),
+                                Some(tile) => layer.set_tile(x as i32, y as i32, tile.clone()),
+                                None => return Err(Error::UnknownTile(number, x + 1, code))
+                            }
+                        }
+                    }
+
+                    if let Some(&(number, line)) = lines.peek() {
+                        if !line.trim().is_empty() {
+                            return Err(Error::Dimensions(number, format!("expected {} rows, found more: {}", height, line)));
+                        }
+                    }
+                },
+                "image" => {
+                    if words.len() < 4 {
+                        return Err(Error::Syntax(number, "expected `image <name> <background|foreground> <path> [options]`".to_string()));
+                    }
+
+                    let pass = parse_pass(number, words[2])?;
+                    let options = parse_options(number, &words[4..])?;
+
+                    if pass == Pass::Collision {
+                        return Err(Error::Syntax(number, "images can not be in the collision pass".to_string()));
+                    }
+
+                    let image = Image {
+                        path: words[3].to_string(),
+                        x: options.offset.0,
+                        y: options.offset.1,
+                        scale: options.scale
+                    };
+
+                    match map {
+                        Some(ref mut map) => map.add_image(pass, image, options.parallax),
+                        None => return Err(Error::Syntax(number, "`size` must come before `image`".to_string()))
+                    }
+                },
+                word => return Err(Error::Syntax(number, format!("unexpected `{}`", word)))
+            }
+        }
+
+        map.filter(|map| !map.layers().is_empty())
+            .ok_or_else(|| Error::Syntax(source.lines().count(), "missing `layer`".to_string()))
+    }
+
+    fn parse_legend(&self, number: usize, line: &str) -> Result<(char, Tile), Error> {
+        let mut chars = line.chars();
+
+        let code = chars.next().unwrap();
+        let words = chars.as_str().split_whitespace().collect::<Vec<_>>();
+
+        match words.len() {
+            0 => Err(Error::Syntax(number, format!("missing tile for '{}'", code))),
+            1 if words[0] == "empty" => Ok((code, Tile::Empty)),
+            1 => match self.registry.get(words[0]) {
+                Some(kind) => Ok((code, Tile::Defined(kind.clone()))),
+                None => Err(Error::UnknownType(number, words[0].to_string()))
+            },
+            _ => Err(Error::Syntax(number, format!("invalid tile `{}`", words.join(" "))))
+        }
+    }
+}
+
+
+struct Options {
+    parallax: Parallax,
+    width: Option<u32>,
+    offset: (i32, i32),
+    scale: f32
+}
+
+fn parse_options(number: usize, words: &[&str]) -> Result<Options, Error> {
+    let mut options = Options {
+        parallax: Parallax::none(),
+        width: None,
+        offset: (0, 0),
+        scale: 1.0
+    };
+
+    let mut words = words.iter();
+
+    while let Some(word) = words.next() {
+        let mut next = || words.next().copied().unwrap_or("");
+
+        match *word {
+            "scroll" => {
+                options.parallax.scroll_x = parse_number(number, next())?;
+                options.parallax.scroll_y = parse_number(number, next())?;
+            },
+            "repeat" => options.parallax.repeat = true,
+            "width" => options.width = Some(parse_number(number, next())?),
+            "offset" => options.offset = (parse_number(number, next())?, parse_number(number, next())?),
+            "scale" => options.scale = parse_number(number, next())?,
+            word => return Err(Error::Syntax(number, format!("unknown option `{}`", word)))
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_pass(number: usize, word: &str) -> Result<Pass, Error> {
+    Pass::from_name(word).ok_or_else(|| Error::Syntax(number, format!("unknown pass `{}`", word)))
+}
+
+fn parse_number<T: FromStr>(number: usize, word: &str) -> Result<T, Error> {
+    word.parse().map_err(|_| Error::Syntax(number, format!("expected a number, found `{}`", word)))
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::rect::Rect;
+
+    use map::{LayerData, Edge};
+
+    use super::*;
+
+    #[test]
+    fn loader_parse() {
+        let mut registry = Registry::new(2, 2);
+
+        registry.parse("floor 1 1 solid\n").ok().unwrap();
+
+        let loader = Loader::new(&registry, 3, 3);
+
+        let map = loader.parse("size 3 2\n\nlegend\n. empty\n# floor\n\nlayer sky background\n...\n...\n\nlayer ground collision\n..#\n###\n").ok().unwrap();
+
+        assert_eq!(map.to_rect(), Rect::new(0, 0, 9, 6));
+        assert_eq!(map.layers().len(), 2);
+        assert_eq!(map.layers()[1].pass, Pass::Collision);
+
+        assert_eq!(map.shape(0, 0), Shape::Empty);
+        assert_eq!(map.shape(2, 0), Shape::Solid);
+        assert_eq!(map.edges, Edges::walls());
+
+        match map.layers()[1].tiles().unwrap().get_tile(2, 0).kind() {
+            Some(kind) => assert_eq!((kind.name.as_str(), kind.source), ("floor", Rect::new(16, 16, 16, 16))),
+            None => panic!("expected floor")
+        }
+
+        let map = loader.parse("size 3 2\nlegend\n. empty\n\nimage sky background gfx/sky.png scroll 0 0 offset 0 -8 scale 2\n\nlayer hills background scroll 0.5 1 repeat width 2\n..\n..\n").ok().unwrap();
+
+        assert_eq!(map.layers()[0].parallax, Parallax::new(0.0, 0.0, false));
+        assert_eq!(map.layers()[1].parallax, Parallax::new(0.5, 1.0, true));
+        assert_eq!(map.layers()[1].width, 2);
+
+        match map.layers()[0].data {
+            LayerData::Image(ref image) => assert_eq!((image.path.as_str(), image.y, image.scale), ("gfx/sky.png", -8, 2.0)),
+            _ => panic!("expected image")
+        }
+
+        let map = loader.parse("size 3 2\nedges empty clamp solid empty\nobject goomba 2 1 turn-at-ledges true\nobject area 0 0 width 3 height 2 name outside\nlegend\n. empty\n\nlayer ground collision\n...\n...\n").ok().unwrap();
+
+        assert_eq!(map.edges, Edges::new(Edge::Empty, Edge::Clamp, Edge::Solid, Edge::Empty));
+        assert_eq!((map.objects[0].kind.as_str(), map.objects[0].x, map.objects[0].y), ("goomba", 6.0, 3.0));
+        assert_eq!(map.objects[0].properties["turn-at-ledges"], "true");
+        assert_eq!((map.objects[0].width, map.objects[0].height), (3.0, 3.0));
+        assert_eq!((map.objects[1].name.as_str(), map.objects[1].width, map.objects[1].height), ("outside", 9.0, 6.0));
+
+        match loader.parse("size 3 2\nobject goomba 2\n") {
+            Err(Error::Syntax(2, _)) => (),
+            _ => panic!("expected invalid object")
+        }
+
+        match loader.parse("size 3 2\nedges solid solid\n") {
+            Err(Error::Syntax(2, _)) => (),
+            _ => panic!("expected invalid edges")
+        }
+
+        match loader.parse("size 3 2\nlegend\n. empty\n\nlayer ground collision scroll 0.5 1\n...\n...\n") {
+            Err(Error::Syntax(5, _)) => (),
+            _ => panic!("expected scrolling collision layer to fail")
+        }
+
+        match loader.parse("size 3 2\nlegend\n. empty\n\nlayer ground collision\n..x\n...\n") {
+            Err(Error::UnknownTile(6, 3, 'x')) => (),
+            _ => panic!("expected unknown tile")
+        }
+
+        match loader.parse("size 3 2\nlegend\n. empty\n\nlayer ground collision\n....\n...\n") {
+            Err(Error::Dimensions(6, _)) => (),
+            _ => panic!("expected wrong dimensions")
+        }
+
+        match loader.parse("size 3 2\nlegend\n. empty\n\nlayer ground collision\n...\n") {
+            Err(Error::Dimensions(_, _)) => (),
+            _ => panic!("expected wrong dimensions")
+        }
+
+        match loader.parse("size 3 2\nlegend\n. brick\n\nlayer ground collision\n...\n...\n") {
+            Err(Error::UnknownType(3, _)) => (),
+            _ => panic!("expected unknown tile type")
+        }
+    }
+}
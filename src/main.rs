@@ -1,30 +1,69 @@
 extern crate sdl2;
 extern crate sdl2_image;
-
+extern crate xml;
+extern crate serde_json;
+extern crate base64;
+extern crate flate2;
+
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
 use std::path::Path;
+use std::process;
 
 use sdl2_image::LoadTexture;
 use sdl2::rect::Rect;
 use sdl2::keyboard::Keycode;
 use sdl2::pixels::Color;
+use sdl2::render::{Renderer, Texture};
 
-use tile::Layer;
+use level::{Loader, Tile};
+use registry::Registry;
+use map::{Map, Pass, LayerData, Object};
+use tiled::Importer;
 use camera::Camera;
 use keyboard::KeyboardHandler;
-use sprite::{Sprite, StaticSprite};
 use timer::Timer;
-use game_object::GameObject;
-use player_components::{PlayerPhysicsComponent, PlayerGraphicsComponent};
+use game_object::{Pose, Power};
+use world::{World, Entity};
+use contact::Side;
+use component::{TICK, Input, Context, Touch};
+use player_components::{PlayerGraphicsComponent, player_collider};
+use enemy_components::{WalkerGraphicsComponent, ShellGraphicsComponent};
+use item_components::{Item, ItemGraphicsComponent};
+use block_components::Blocks;
+use projectile_components::{Projectile, Launcher, CannonGraphicsComponent};
+use goal_components::Goal;
+use warp::Warp;
+use session::GameSession;
+use manifest::{Manifest, Entry};
 
 
 mod timer;
 mod tile;
+mod level;
+mod registry;
+mod map;
+mod collision;
+mod tiled;
 mod camera;
 mod keyboard;
 mod sprite;
 mod game_object;
 mod component;
 mod player_components;
+mod enemy_components;
+mod item_components;
+mod block_components;
+mod projectile_components;
+mod goal_components;
+mod warp;
+mod world;
+mod session;
+mod manifest;
+mod contact;
+#[cfg(test)]
+mod test_util;
 
 
 const SCREEN_WIDTH : u32 = 960;
@@ -33,23 +72,28 @@ const SCREEN_HEIGHT : u32 = 640;
 const TILE_WIDTH : u32 = 32;
 const TILE_HEIGHT : u32 = 32;
 
-const MS_PER_UPDATE : f64 = 10.0;
+// how many fireballs the player can have out at once.
+const FIREBALLS : usize = 2;
 
-const PLAYER_SPEED_X : f32 = 4.0;
-const PLAYER_THRESHOLD_X : f32 = 0.2;
+const LIVES : u32 = 3;
 
-const PLAYER_ACCELERATION_X_START : f32 = 0.02;
-const PLAYER_ACCELERATION_X_STOP : f32 = 0.15;
-const PLAYER_ACCELERATION_X_CHANGE : f32 = 0.06;
+// how long a unit of the time to finish a level lasts, and how many there
+// are in a level that is played on its own.
+const TIME_UNIT : f64 = 400.0;
+const TIME : u32 = 400;
 
+// how long the player walks off to the exit at most, and how long the game
+// waits after the end of a level, before the next one.
+const WALK_OFF_TIME : f64 = 4000.0;
+const FINISH_TIME : f64 = 1000.0;
 
-#[derive(Clone)]
-enum Tile<'a> {
-    Empty,
-    Static(&'a StaticSprite<'a>, bool),
-    Background(Rect),
-    Floor(Rect)
-}
+// how long the player takes to go into a pipe or a door, and how fast it
+// goes in.
+const ENTER_TIME : f64 = 700.0;
+const ENTER_SPEED : f32 = 1.0;
+
+// how long the game waits after it is over, before it starts again.
+const GAME_OVER_TIME : f64 = 3000.0;
 
 
 fn main() {
@@ -63,111 +107,63 @@ fn main() {
 
     let world_sprites = renderer.load_texture(Path::new("gfx/world.png")).unwrap();
 
-    let floor_sprite = StaticSprite::new(&world_sprites, 16 * 0, 16 * 0);
-    let brick_sprite = StaticSprite::new(&world_sprites, 16 * 1, 16 * 0);
-
-    let player_sprites = renderer.load_texture(Path::new("gfx/mario.png")).unwrap();
+    let sprites = Sprites {
+        player: renderer.load_texture(Path::new("gfx/mario.png")).unwrap(),
+        enemies: renderer.load_texture(Path::new("gfx/enemies.png")).unwrap(),
+        items: renderer.load_texture(Path::new("gfx/items.png")).unwrap(),
+        projectiles: renderer.load_texture(Path::new("gfx/projectiles.png")).unwrap()
+    };
 
     let timer = Timer::new();
 
-    let mut player = GameObject::new(390.0, 390.0, Box::new(PlayerPhysicsComponent::new()), Box::new(PlayerGraphicsComponent::new(&player_sprites)));
-
     let mut keyboard = KeyboardHandler::new();
+    let mut input = Input::default();
+
+    let world_query = world_sprites.query();
 
-    let mut layer = Layer::new(212, 20, TILE_WIDTH, TILE_HEIGHT, Tile::Empty);
-
-    layer.set_tile(2, 15, Tile::Background(Rect::new(16 * 9, 16 * 8, 16, 16)));
-
-    layer.set_tile(1, 16, Tile::Background(Rect::new(16 * 8, 16 * 8, 16, 16)));
-    layer.set_tile(2, 16, Tile::Background(Rect::new(16 * 8, 16 * 9, 16, 16)));
-    layer.set_tile(3, 16, Tile::Background(Rect::new(16 * 10, 16 * 8, 16, 16)));
-
-    layer.set_tile(0, 17, Tile::Background(Rect::new(16 * 8, 16 * 8, 16, 16)));
-    layer.set_tile(1, 17, Tile::Background(Rect::new(16 * 8, 16 * 9, 16, 16)));
-    layer.set_tile(2, 17, Tile::Background(Rect::new(16 * 9, 16 * 9, 16, 16)));
-    layer.set_tile(3, 17, Tile::Background(Rect::new(16 * 8, 16 * 9, 16, 16)));
-    layer.set_tile(4, 17, Tile::Background(Rect::new(16 * 10, 16 * 8, 16, 16)));
-
-    layer.set_tile(11, 17, Tile::Background(Rect::new(16 * 11, 16 * 9, 16, 16)));
-    layer.set_tile(12, 17, Tile::Background(Rect::new(16 * 12, 16 * 9, 16, 16)));
-    layer.set_tile(13, 17, Tile::Background(Rect::new(16 * 12, 16 * 9, 16, 16)));
-    layer.set_tile(14, 17, Tile::Background(Rect::new(16 * 12, 16 * 9, 16, 16)));
-    layer.set_tile(15, 17, Tile::Background(Rect::new(16 * 13, 16 * 9, 16, 16)));
-
-    layer.set_tile(16, 14, Tile::Floor(Rect::new(16 * 24, 16 * 0, 16, 16)));
-
-    layer.set_tile(17, 16, Tile::Background(Rect::new(16 * 9, 16 * 8, 16, 16)));
-
-    layer.set_tile(16, 17, Tile::Background(Rect::new(16 * 8, 16 * 8, 16, 16)));
-    layer.set_tile(17, 17, Tile::Background(Rect::new(16 * 8, 16 * 9, 16, 16)));
-    layer.set_tile(18, 17, Tile::Background(Rect::new(16 * 10, 16 * 8, 16, 16)));
-
-    layer.set_tile(20, 14, Tile::Static(&brick_sprite, true));
-    layer.set_tile(21, 14, Tile::Floor(Rect::new(16 * 24, 16 * 0, 16, 16)));
-    layer.set_tile(22, 14, Tile::Static(&brick_sprite, true));
-    layer.set_tile(23, 14, Tile::Floor(Rect::new(16 * 24, 16 * 0, 16, 16)));
-    layer.set_tile(24, 14, Tile::Static(&brick_sprite, true));
-
-    layer.set_tile(22, 10, Tile::Floor(Rect::new(16 * 24, 16 * 0, 16, 16)));
-
-    layer.set_tile(19, 7, Tile::Floor(Rect::new(16 * 0, 16 * 20, 16, 16)));
-    layer.set_tile(20, 7, Tile::Floor(Rect::new(16 * 1, 16 * 20, 16, 16)));
-    layer.set_tile(21, 7, Tile::Floor(Rect::new(16 * 2, 16 * 20, 16, 16)));
-    layer.set_tile(19, 8, Tile::Floor(Rect::new(16 * 0, 16 * 21, 16, 16)));
-    layer.set_tile(20, 8, Tile::Floor(Rect::new(16 * 1, 16 * 21, 16, 16)));
-    layer.set_tile(21, 8, Tile::Floor(Rect::new(16 * 2, 16 * 21, 16, 16)));
-
-    layer.set_tile(23, 17, Tile::Background(Rect::new(16 * 11, 16 * 9, 16, 16)));
-    layer.set_tile(24, 17, Tile::Background(Rect::new(16 * 12, 16 * 9, 16, 16)));
-    layer.set_tile(25, 17, Tile::Background(Rect::new(16 * 13, 16 * 9, 16, 16)));
-
-    layer.set_tile(28, 16, Tile::Floor(Rect::new(16 * 0, 16 * 8, 16, 16)));
-    layer.set_tile(29, 16, Tile::Floor(Rect::new(16 * 1, 16 * 8, 16, 16)));
-    layer.set_tile(28, 17, Tile::Floor(Rect::new(16 * 0, 16 * 9, 16, 16)));
-    layer.set_tile(29, 17, Tile::Floor(Rect::new(16 * 1, 16 * 9, 16, 16)));
-
-    layer.set_tile(38, 15, Tile::Floor(Rect::new(16 * 0, 16 * 8, 16, 16)));
-    layer.set_tile(39, 15, Tile::Floor(Rect::new(16 * 1, 16 * 8, 16, 16)));
-    layer.set_tile(38, 16, Tile::Floor(Rect::new(16 * 0, 16 * 9, 16, 16)));
-    layer.set_tile(39, 16, Tile::Floor(Rect::new(16 * 1, 16 * 9, 16, 16)));
-    layer.set_tile(38, 17, Tile::Floor(Rect::new(16 * 0, 16 * 9, 16, 16)));
-    layer.set_tile(39, 17, Tile::Floor(Rect::new(16 * 1, 16 * 9, 16, 16)));
-
-    layer.set_tile(41, 17, Tile::Background(Rect::new(16 * 11, 16 * 9, 16, 16)));
-    layer.set_tile(42, 17, Tile::Background(Rect::new(16 * 12, 16 * 9, 16, 16)));
-    layer.set_tile(43, 17, Tile::Background(Rect::new(16 * 12, 16 * 9, 16, 16)));
-    layer.set_tile(44, 17, Tile::Background(Rect::new(16 * 13, 16 * 9, 16, 16)));
-
-    layer.set_tile(46, 14, Tile::Floor(Rect::new(16 * 0, 16 * 8, 16, 16)));
-    layer.set_tile(47, 14, Tile::Floor(Rect::new(16 * 1, 16 * 8, 16, 16)));
-    layer.set_tile(46, 15, Tile::Floor(Rect::new(16 * 0, 16 * 9, 16, 16)));
-    layer.set_tile(47, 15, Tile::Floor(Rect::new(16 * 1, 16 * 9, 16, 16)));
-    layer.set_tile(46, 16, Tile::Floor(Rect::new(16 * 0, 16 * 9, 16, 16)));
-    layer.set_tile(47, 16, Tile::Floor(Rect::new(16 * 1, 16 * 9, 16, 16)));
-    layer.set_tile(46, 17, Tile::Floor(Rect::new(16 * 0, 16 * 9, 16, 16)));
-    layer.set_tile(47, 17, Tile::Floor(Rect::new(16 * 1, 16 * 9, 16, 16)));
-
-    layer.set_tile(50, 15, Tile::Background(Rect::new(16 * 9, 16 * 8, 16, 16)));
-
-    layer.set_tile(49, 16, Tile::Background(Rect::new(16 * 8, 16 * 8, 16, 16)));
-    layer.set_tile(50, 16, Tile::Background(Rect::new(16 * 8, 16 * 9, 16, 16)));
-    layer.set_tile(51, 16, Tile::Background(Rect::new(16 * 10, 16 * 8, 16, 16)));
-
-    layer.set_tile(48, 17, Tile::Background(Rect::new(16 * 8, 16 * 8, 16, 16)));
-    layer.set_tile(49, 17, Tile::Background(Rect::new(16 * 8, 16 * 9, 16, 16)));
-    layer.set_tile(50, 17, Tile::Background(Rect::new(16 * 9, 16 * 9, 16, 16)));
-    layer.set_tile(51, 17, Tile::Background(Rect::new(16 * 8, 16 * 9, 16, 16)));
-    layer.set_tile(52, 17, Tile::Background(Rect::new(16 * 10, 16 * 8, 16, 16)));
-
-    for x in 0..212 {
-        layer.set_tile(x, 18, Tile::Static(&floor_sprite, true));
-        layer.set_tile(x, 19, Tile::Static(&floor_sprite, true));
+    let mut registry = Registry::new(world_query.width / 16, world_query.height / 16);
+
+    if let Err(err) = registry.load("gfx/world.txt") {
+        eprintln!("gfx/world.txt: {}", err);
+
+        process::exit(1);
     }
 
-    let mut camera = Camera::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT, layer.to_rect());
+    let loader = Loader::new(&registry, TILE_WIDTH, TILE_HEIGHT);
+    let importer = Importer::new("gfx/world.png", TILE_WIDTH, TILE_HEIGHT);
+
+    // plays the levels of the manifest in order, or only the one given.
+    let manifest = match env::args().nth(1) {
+        Some(path) => Manifest::single(&path, TIME),
+        None => match Manifest::load("levels/world.txt") {
+            Ok(manifest) => manifest,
+            Err(err) => {
+                eprintln!("levels/world.txt: {}", err);
+
+                process::exit(1);
+            }
+        }
+    };
+
+    let mut level = 0;
+    let mut entry = manifest.get(level).unwrap();
+
+    let mut stage = load_stage(entry, &loader, &importer, None, &sprites);
+    let mut checkpoint = None;
+
+    let mut images = HashMap::new();
+
+    load_images(&stage.map, &mut images, &renderer);
+
+    let mut session = GameSession::new(entry.world, entry.level, LIVES);
+    let mut title = String::new();
+    let mut game_over = 0.0;
+
+    let mut camera = Camera::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT, stage.bounds());
 
     let mut previous = timer.current_time();
     let mut lag = 0.0;
+    let mut clock = 0.0;
 
     let mut event_pump = sdl_context.event_pump().unwrap();
 
@@ -177,6 +173,7 @@ fn main() {
 
         previous = current;
         lag += elapsed;
+        clock += elapsed;
 
         keyboard.clear();
 
@@ -184,7 +181,7 @@ fn main() {
             use sdl2::event::Event;
 
             match event {
-                Event::KeyDown {keycode, ..} if keycode == Some(Keycode::Escape) => break 'main,
+                Event::KeyDown {keycode: Some(Keycode::Escape), ..} => break 'main,
                 Event::Quit {..} => break 'main,
                 _ => (),
             }
@@ -192,213 +189,605 @@ fn main() {
             keyboard.process(&event);
         }
 
-        if keyboard.is_held(Keycode::Right) && (player.dx >= 0.0 || player.on_ground) {
-            let a = if player.dx > 0.0 {
-                PLAYER_ACCELERATION_X_START
-            } else {
-                PLAYER_ACCELERATION_X_CHANGE
-            };
+        input.left = keyboard.is_held(Keycode::Left);
+        input.right = keyboard.is_held(Keycode::Right);
+        input.up = keyboard.is_held(Keycode::Up);
+        input.down = keyboard.is_held(Keycode::Down);
+        input.action = keyboard.is_held(Keycode::Space);
+        input.action_pressed |= keyboard.was_pressed(Keycode::Space);
+        input.jump |= keyboard.was_pressed(Keycode::Up);
+        input.jump_released |= keyboard.was_released(Keycode::Up);
+
+        while lag >= TICK {
+            if game_over > 0.0 {
+                game_over -= TICK;
+                lag -= TICK;
+
+                if game_over <= 0.0 {
+                    level = 0;
+                    entry = manifest.get(level).unwrap();
+
+                    session = GameSession::new(entry.world, entry.level, LIVES);
+                    checkpoint = None;
 
-            player.dx = a * PLAYER_SPEED_X + (1.0 - a) * player.dx;
-        } else if keyboard.is_held(Keycode::Left) && (player.dx <= 0.0 || player.on_ground) {
-            let a = if player.dx < 0.0 {
-                PLAYER_ACCELERATION_X_START
-            } else {
-                PLAYER_ACCELERATION_X_CHANGE
+                    stage = load_stage(entry, &loader, &importer, None, &sprites);
+
+                    load_images(&stage.map, &mut images, &renderer);
+
+                    camera = Camera::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT, stage.bounds());
+                }
+
+                continue;
+            }
+
+            // the player is not in control at the end of the level.
+            let control = match stage.ending {
+                Some(Ending::Walking(_)) => Input { right: true, ..Input::default() },
+                Some(_) => Input::default(),
+                None => input
             };
 
-            player.dx = a * -PLAYER_SPEED_X + (1.0 - a) * player.dx;
-        } else if player.on_ground {
-            player.dx *= 1.0 - PLAYER_ACCELERATION_X_STOP;
+            let target = stage.world.transform(stage.player).map(|transform| transform.to_rect());
+            let mut events = stage.world.update(&Context { map: &stage.map, input: control, player: target, dt: TICK });
+
+            input.jump = false;
+            input.jump_released = false;
+            input.action_pressed = false;
+
+            let shots = events.iter().filter_map(|&(entity, event)| match event {
+                component::Event::Shot(kind) => Some((entity, kind)),
+                _ => None
+            }).collect::<Vec<_>>();
+
+            for (entity, kind) in shots {
+                let projectile = match stage.world.transform(entity) {
+                    Some(shooter) => projectile_components::shoot(kind, shooter, &sprites.projectiles),
+                    None => continue
+                };
+
+                if entity == stage.player {
+                    stage.fireballs.launch(&mut stage.world, projectile);
+                } else {
+                    stage.world.spawn(projectile);
+                }
+            }
+
+            let bumped = events.iter().filter_map(|&(entity, event)| match event {
+                component::Event::Bumped(x, y) if entity == stage.player => Some((x, y)),
+                _ => None
+            }).collect::<Vec<_>>();
+
+            let big = stage.world.health(stage.player).map(|health| health.power != Power::Small).unwrap_or(false);
+
+            for (x, y) in bumped {
+                let hit = match stage.blocks.hit(&mut stage.map, x, y, big) {
+                    Some(hit) => hit,
+                    None => continue
+                };
+
+                // knocks out enemies and collects coins on top of the block.
+                let block = Rect::new(x * TILE_WIDTH as i32, y * TILE_HEIGHT as i32, TILE_WIDTH, TILE_HEIGHT);
+                let above = Rect::new(block.x(), block.y() - TILE_HEIGHT as i32, TILE_WIDTH, TILE_HEIGHT);
+                let touch = Touch { entity: None, side: Side::Top, layer: contact::BLOCK, rect: block };
+
+                events.extend(stage.world.touch_area(&above, &touch, &Context { map: &stage.map, input: control, player: target, dt: TICK }));
+
+                take_coins(&mut stage.map, &above, stage.player, &mut events);
+
+                // blocks give the big player a flower instead of a mushroom.
+                match hit.contents {
+                    Some(Item::Mushroom) if big => {
+                        stage.world.spawn(item_components::from_block(block.x() as f32, block.y() as f32, Item::Flower, TILE_HEIGHT, Box::new(ItemGraphicsComponent::new(&sprites.items, Item::Flower))));
+                    },
+                    Some(Item::Coin) => {
+                        stage.world.spawn(item_components::from_block(above.x() as f32, above.y() as f32, Item::Coin, TILE_HEIGHT, Box::new(ItemGraphicsComponent::new(&sprites.items, Item::Coin))));
+                    },
+                    Some(item) => {
+                        stage.world.spawn(item_components::from_block(block.x() as f32, block.y() as f32, item, TILE_HEIGHT, Box::new(ItemGraphicsComponent::new(&sprites.items, item))));
+                    },
+                    None => ()
+                }
+
+                if hit.shattered {
+                    events.push((stage.player, component::Event::Shattered));
 
-            if player.dx.abs() <= PLAYER_THRESHOLD_X {
-                player.dx = 0.0;
+                    for piece in block_components::debris(block.x() as f32, block.y() as f32, TILE_WIDTH, &sprites.items) {
+                        stage.world.spawn(piece);
+                    }
+                }
             }
+
+            stage.blocks.update(TICK);
+
+            if let Some(rect) = stage.world.transform(stage.player).filter(|_| stage.world.pose(stage.player) != Some(Pose::Dying)).map(|transform| transform.to_rect()) {
+                take_coins(&mut stage.map, &rect, stage.player, &mut events);
+
+                // the furthest checkpoint passed is where the player starts
+                // over.
+                for &position in stage.checkpoints.iter() {
+                    if rect.x() as f32 >= position.0 && checkpoint.map(|(x, _)| position.0 > x).unwrap_or(true) {
+                        checkpoint = Some(position);
+                    }
+                }
+            }
+
+            session.award(&events, stage.player);
+
+            if let Some(transform) = stage.world.transform(stage.player) {
+                camera.center(&transform.to_rect());
+            }
+
+            // starts the level over, with everything in it back in place, or
+            // ends the game after the last life.
+            if events.contains(&(stage.player, component::Event::Died)) {
+                if session.lose_life() {
+                    game_over = GAME_OVER_TIME;
+                } else {
+                    stage = load_stage(entry, &loader, &importer, checkpoint, &sprites);
+
+                    camera.set_bounding(stage.bounds());
+                }
+            }
+
+            // the clock runs until the player reaches the goal, and the
+            // player dies when it runs out.
+            if stage.ending.is_none() && stage.time > 0.0 {
+                stage.time -= TICK / TIME_UNIT;
+
+                if stage.time <= 0.0 {
+                    stage.time = 0.0;
+
+                    if let Some(health) = stage.world.health_mut(stage.player) {
+                        health.kill();
+                    }
+                }
+            }
+
+            // takes the player through a warp, to another part of the level or
+            // to another level, with the power and the time that it had.
+            if let Some(warp) = stage.warp(&control, TICK) {
+                let power = stage.world.health(stage.player).map(|health| health.power).unwrap_or(Power::Small);
+
+                if let Some(ref path) = warp.level {
+                    let time = stage.time;
+
+                    stage = load_stage(&Entry { path: path.clone(), ..entry.clone() }, &loader, &importer, None, &sprites);
+                    stage.time = time;
+
+                    load_images(&stage.map, &mut images, &renderer);
+                }
+
+                if !stage.arrive(&warp.to, power) {
+                    eprintln!("{}: no `{}` to warp to", warp.level.as_ref().unwrap_or(&entry.path), warp.to);
+
+                    process::exit(1);
+                }
+
+                camera.set_bounding(stage.bounds());
+
+                if let Some(transform) = stage.world.transform(stage.player) {
+                    camera.center(&transform.to_rect());
+                }
+            }
+
+            // goes on to the next level, and back to the first after the last.
+            if stage.finish(&mut session, TICK) {
+                level = if manifest.get(level + 1).is_some() { level + 1 } else { 0 };
+                entry = manifest.get(level).unwrap();
+
+                session.world = entry.world;
+                session.level = entry.level;
+                checkpoint = None;
+
+                stage = load_stage(entry, &loader, &importer, None, &sprites);
+
+                load_images(&stage.map, &mut images, &renderer);
+
+                camera = Camera::new(0, 0, SCREEN_WIDTH, SCREEN_HEIGHT, stage.bounds());
+            }
+
+            lag -= TICK;
         }
 
-        if player.on_ground && keyboard.was_pressed(Keycode::Up) {
-            player.dy = -8.0;
+        let status = if game_over > 0.0 {
+            "Super Matte Bros  GAME OVER".to_string()
+        } else {
+            format!("Super Matte Bros  {}  TIME {:03}", session, stage.time.ceil() as u32)
+        };
 
-            player.on_ground = false;
+        if status != title {
+            if let Some(window) = renderer.window_mut() {
+                let _ = window.set_title(&status);
+            }
+
+            title = status;
         }
 
-        if keyboard.was_released(Keycode::Up) && player.dy < -4.0 {
-            player.dy = -4.0;
+        renderer.set_draw_color(Color::RGB(93, 148, 251));
+        renderer.clear();
+
+        let context = RenderContext { camera: &camera, tiles: &world_sprites, images: &images, time: clock };
+
+        render_map(&stage.map, &[Pass::Background, Pass::Collision], &stage.blocks, &context, &mut renderer);
+
+        // hides the player as it goes into a pipe.
+        let clip = stage.warping.as_ref().and_then(|(warp, _)| warp.visible(&camera.to_rect())).map(|rect| camera.to_relative_rect(&rect));
+
+        renderer.set_clip_rect(clip);
+
+        stage.world.render(elapsed, &mut renderer, &camera);
+
+        renderer.set_clip_rect(None);
+
+        render_map(&stage.map, &[Pass::Foreground], &stage.blocks, &context, &mut renderer);
+
+        renderer.present();
+    }
+}
+
+
+
+// The textures of the objects in a level.
+struct Sprites {
+    player: Texture,
+    enemies: Texture,
+    items: Texture,
+    projectiles: Texture
+}
+
+
+// A level that is being played, and everything in it.
+struct Stage<'a> {
+    map: Map,
+    blocks: Blocks,
+    world: World<'a>,
+    player: Entity,
+    fireballs: Launcher,
+    checkpoints: Vec<(f32, f32)>,
+    goal: Option<Goal>,
+    flag: Option<Entity>,
+    exit: Option<f32>,
+    warps: Vec<Warp>,
+    time: f64,
+    ending: Option<Ending>,
+    warping: Option<(Warp, f64)>
+}
+
+impl<'a> Stage<'a> {
+    // Loads the level of `entry` with the player at `start`, or where the
+    // level says that it starts.
+    fn load(entry: &Entry, loader: &Loader, importer: &Importer, start: Option<(f32, f32)>, sprites: &'a Sprites) -> Result<Stage<'a>, Box<dyn Error>> {
+        let map = load_level(&entry.path, loader, importer)?;
+        let blocks = Blocks::new(&map)?;
+
+        let start = start.unwrap_or_else(|| match map.objects.iter().find(|object| object.kind == "player") {
+            Some(spawn) => (spawn.x, spawn.y),
+            None => (390.0, 390.0)
+        });
+
+        let mut world = World::new(TILE_WIDTH);
+
+        let player = world.spawn(player_components::player(start.0, start.1, Box::new(PlayerGraphicsComponent::new(&sprites.player))));
+
+        for object in map.objects.iter() {
+            spawn_object(&mut world, object, sprites);
         }
 
-        while lag >= MS_PER_UPDATE {
-            player.dy += player.gravity;
+        let checkpoints = map.objects.iter().filter(|object| object.kind == "checkpoint").map(|object| (object.x, object.y)).collect();
+
+        let goal = map.objects.iter().find(|object| object.kind == "goal").map(|object| Goal::new(&map, object));
+        let flag = goal.map(|goal| world.spawn(goal_components::flag(&goal, TILE_WIDTH, &sprites.items)));
+        let exit = map.objects.iter().find(|object| object.kind == "exit").map(|object| object.x);
+
+        let mut warps = Vec::new();
+
+        for object in map.objects.iter().filter(|object| object.kind == "warp") {
+            let warp = match Warp::new(object) {
+                Some(warp) => warp,
+                None => return Err(format!("warp at {}, {} needs `to` and an `enter` of `down`, `right` or `door`", object.x, object.y).into())
+            };
 
-            if player.dy > 8.0 {
-                player.dy = 8.0;
-            } else if player.dy < -8.0 {
-                player.dy = -8.0;
+            if warp.level.is_none() && !map.objects.iter().any(|destination| destination.name == warp.to) {
+                return Err(format!("no `{}` to warp to", warp.to).into());
             }
 
-            player.on_ground = false;
+            warps.push(warp);
+        }
 
-            if let Some(intersect) = layer.find_intersecting(&player.to_rect()) {
-                if player.dx > 0.0 {
-                    let p = player.x + player.w as f32;
-                    let mut d = player.dx;
+        Ok(Stage {
+            map: map,
+            blocks: blocks,
+            world: world,
+            player: player,
+            fireballs: Launcher::new(FIREBALLS),
+            checkpoints: checkpoints,
+            goal: goal,
+            flag: flag,
+            exit: exit,
+            warps: warps,
+            time: entry.time as f64,
+            ending: None,
+            warping: None
+        })
+    }
 
-                    for y in intersect.y()..(intersect.y() + intersect.height() as i32) {
-                        let mut x = intersect.x();
+    // The part of the level that the player is in, which the camera keeps
+    // to.
+    fn bounds(&self) -> Rect {
+        match self.world.transform(self.player) {
+            Some(transform) => warp::bounds(&self.map, transform.x + transform.w as f32 / 2.0, transform.y + transform.h as f32 / 2.0),
+            None => self.map.to_rect()
+        }
+    }
 
-                        loop {
-                            let t = (x * TILE_WIDTH as i32) as f32 - p;
+    // Takes over from the player once it goes into a warp that it stands at,
+    // and returns the warp when it is all the way in.
+    fn warp(&mut self, input: &Input, dt: f64) -> Option<Warp> {
+        if self.ending.is_some() {
+            return None;
+        }
 
-                            if t > d {
-                                break;
-                            }
+        self.warping = match self.warping.take() {
+            None => {
+                let on_ground = self.world.velocity(self.player).map(|velocity| velocity.on_ground).unwrap_or(false);
 
-                            d = match *layer.get_tile(x, y) {
-                                Tile::Floor(_) => d.min(t),
-                                Tile::Static(_, solid) => if solid { d.min(t) } else { d },
-                                _ => d
-                            };
+                let rect = match (self.world.transform(self.player), self.world.pose(self.player)) {
+                    (Some(transform), Some(Pose::Normal)) | (Some(transform), Some(Pose::Blinking)) | (Some(transform), Some(Pose::Shining)) if on_ground => transform.to_rect(),
+                    _ => return None
+                };
 
-                            x += 1;
-                        }
-                    }
+                let warp = match self.warps.iter().find(|warp| warp.enters(&rect, input)) {
+                    Some(warp) => warp.clone(),
+                    None => return None
+                };
 
-                    if d > 0.0 {
-                        player.x += d;
-                    } else if d < 0.0 {
-                        player.x += d;
-                        player.dx = 0.0;
-                    } else {
-                        player.dx = 0.0;
-                    }
-                } else if player.dx < 0.0 {
-                    let p = player.x;
-                    let mut d = player.dx;
+                let (dx, dy) = warp.entrance.direction();
 
-                    for y in intersect.y()..(intersect.y() + intersect.height() as i32) {
-                        let mut x = intersect.x();
+                self.world.set_pose(self.player, Pose::Entering);
+                self.world.set_collider(self.player, None);
 
-                        loop {
-                            let t = (x * TILE_WIDTH as i32 + TILE_WIDTH as i32) as f32 - p;
+                if let Some(velocity) = self.world.velocity_mut(self.player) {
+                    velocity.dx = dx * ENTER_SPEED;
+                    velocity.dy = dy * ENTER_SPEED;
+                }
 
-                            if t < d {
-                                break;
-                            }
+                Some((warp, 0.0))
+            },
+            // the clock can still run out on the way in.
+            Some(_) if self.world.pose(self.player) != Some(Pose::Entering) => None,
+            Some((warp, time)) if time >= ENTER_TIME => return Some(warp),
+            Some((warp, time)) => Some((warp, time + dt))
+        };
 
-                            d = match *layer.get_tile(x, y) {
-                                Tile::Floor(_) => d.max(t),
-                                Tile::Static(_, solid) => if solid { d.max(t) } else { d },
-                                _ => d
-                            };
+        None
+    }
 
-                            x -= 1;
-                        }
-                    }
+    // Brings the player out standing at the bottom of the object named
+    // `name`, with `power`. Returns false if there is no such object.
+    fn arrive(&mut self, name: &str, power: Power) -> bool {
+        let (x, y) = match self.map.objects.iter().find(|object| object.name == name) {
+            Some(destination) => (destination.x, destination.y + destination.height),
+            None => return false
+        };
+
+        if let Some(transform) = self.world.transform_mut(self.player) {
+            transform.x = x;
+            transform.y = y - transform.h as f32;
+        }
 
-                    if d < 0.0 {
-                        player.x += d;
-                    } else if d > 0.0 {
-                        player.x += d;
-                        player.dx = 0.0;
-                    } else {
-                        player.dx = 0.0;
-                    }
+        if let Some(health) = self.world.health_mut(self.player) {
+            health.power = power;
+        }
+
+        if let Some(velocity) = self.world.velocity_mut(self.player) {
+            velocity.dx = 0.0;
+            velocity.dy = 0.0;
+        }
+
+        self.world.set_pose(self.player, Pose::Normal);
+        self.world.set_collider(self.player, Some(player_collider()));
+
+        true
+    }
+
+    // Takes over from the player once it touches the pole of the goal, slides
+    // it down and walks it off to the exit, and then turns the time left into
+    // points. Returns true once the level is over.
+    fn finish(&mut self, session: &mut GameSession, dt: f64) -> bool {
+        let goal = match self.goal {
+            Some(goal) => goal,
+            None => return false
+        };
+
+        self.ending = match self.ending {
+            None => {
+                let dying = self.world.pose(self.player) == Some(Pose::Dying);
+
+                match self.world.transform_mut(self.player) {
+                    Some(transform) if !dying && transform.to_rect().has_intersection(goal.to_rect()) => {
+                        transform.x = goal.x - transform.w as f32;
+                        transform.facing = 1.0;
+                    },
+                    _ => return false
                 }
 
-                if player.dy > 0.0 {
-                    let p = player.y + player.h as f32;
-                    let mut d = player.dy;
+                self.world.set_pose(self.player, Pose::Sliding);
+                self.world.set_collider(self.player, None);
 
-                    for x in intersect.x()..(intersect.x() + intersect.width() as i32) {
-                        let mut y = intersect.y();
+                if let Some(flag) = self.flag {
+                    self.world.set_pose(flag, Pose::Sliding);
+                }
 
-                        loop {
-                            let t = (y * TILE_HEIGHT as i32) as f32 - p;
+                self.time = self.time.ceil();
 
-                            if t > d {
-                                break;
-                            }
+                Some(Ending::Sliding)
+            },
+            Some(Ending::Sliding) if self.world.velocity(self.player).map(|velocity| velocity.on_ground).unwrap_or(false) => {
+                self.world.set_pose(self.player, Pose::Normal);
 
-                            d = match *layer.get_tile(x, y) {
-                                Tile::Floor(_) => d.min(t),
-                                Tile::Static(_, solid) => if solid { d.min(t) } else { d },
-                                _ => d
-                            };
+                Some(Ending::Walking(0.0))
+            },
+            Some(Ending::Sliding) => Some(Ending::Sliding),
+            Some(Ending::Walking(time)) => {
+                let exit = self.exit;
+                let arrived = self.world.transform(self.player).map(|transform| exit.map(|x| transform.x >= x).unwrap_or(false)).unwrap_or(true);
 
-                            y += 1;
-                        }
-                    }
+                if arrived || time >= WALK_OFF_TIME {
+                    self.world.destroy(self.player);
 
-                    if d > 0.0 {
-                        player.y += d;
-                    } else if d < 0.0 {
-                        player.y += d;
-                        player.dy = 0.0;
+                    Some(Ending::Tallying)
+                } else {
+                    Some(Ending::Walking(time + dt))
+                }
+            },
+            Some(Ending::Tallying) if self.time > 0.0 => {
+                self.time -= 1.0;
 
-                        player.on_ground = true;
-                    } else {
-                        player.dy = 0.0;
+                session.award_time(1);
 
-                        player.on_ground = true;
-                    }
-                } else if player.dy < 0.0 {
-                    let p = player.y;
-                    let mut d = player.dy;
+                Some(Ending::Tallying)
+            },
+            Some(Ending::Tallying) => Some(Ending::Waiting(0.0)),
+            Some(Ending::Waiting(time)) if time >= FINISH_TIME => return true,
+            Some(Ending::Waiting(time)) => Some(Ending::Waiting(time + dt))
+        };
 
-                    for x in intersect.x()..(intersect.x() + intersect.width() as i32) {
-                        let mut y = intersect.y();
+        false
+    }
+}
 
-                        loop {
-                            let t = (y * TILE_HEIGHT as i32 + TILE_HEIGHT as i32) as f32 - p;
 
-                            if t < d {
-                                break;
-                            }
+// How far the end of a level is, with how long it has been walking or
+// waiting.
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum Ending {
+    Sliding,
+    Walking(f64),
+    Tallying,
+    Waiting(f64)
+}
 
-                            d = match *layer.get_tile(x, y) {
-                                Tile::Floor(_) => d.max(t),
-                                Tile::Static(_, solid) => if solid { d.max(t) } else { d },
-                                _ => d
-                            };
 
-                            y -= 1;
-                        }
-                    }
 
-                    if d < 0.0 {
-                        player.y += d;
-                    } else if d > 0.0 {
-                        player.y += d;
-                        player.dy = 0.0;
-                    } else {
-                        player.dy = 0.0;
-                    }
-                }
+fn load_level(path: &str, loader: &Loader, importer: &Importer) -> Result<Map, Box<dyn Error>> {
+    match Path::new(path).extension().and_then(|extension| extension.to_str()) {
+        Some("tmx") | Some("tmj") => Ok(importer.load(path)?),
+        _ => Ok(loader.load(path)?)
+    }
+}
+
+// Loads the level of `entry`, and exits if it can not.
+fn load_stage<'a>(entry: &Entry, loader: &Loader, importer: &Importer, start: Option<(f32, f32)>, sprites: &'a Sprites) -> Stage<'a> {
+    match Stage::load(entry, loader, importer, start, sprites) {
+        Ok(stage) => stage,
+        Err(err) => {
+            eprintln!("{}: {}", entry.path, err);
+
+            process::exit(1);
+        }
+    }
+}
+
+// Loads the images of `map` that are not loaded yet, and exits if it can not.
+fn load_images(map: &Map, images: &mut HashMap<String, Texture>, renderer: &Renderer) {
+    for image in map.images() {
+        if images.contains_key(&image.path) {
+            continue;
+        }
+
+        match renderer.load_texture(Path::new(&image.path)) {
+            Ok(texture) => images.insert(image.path.clone(), texture),
+            Err(err) => {
+                eprintln!("{}: {}", image.path, err);
+
+                process::exit(1);
             }
+        };
+    }
+}
 
-            player.update();
+// Takes the coin tiles that overlap `area` out of the map, as if the player
+// collected them.
+fn take_coins(map: &mut Map, area: &Rect, player: Entity, events: &mut Vec<(Entity, component::Event)>) {
+    let coins = map.tiles_at(area).into_iter().filter(|&(_, kind)| kind.coin).map(|(position, _)| position).collect::<Vec<_>>();
 
-            camera.center(&player.to_rect());
+    for (x, y) in coins {
+        if map.replace_tile(x, y, Tile::Empty) {
+            events.push((player, component::Event::Collected(Item::Coin)));
+        }
+    }
+}
 
-            lag -= MS_PER_UPDATE;
+fn spawn_object<'a>(world: &mut World<'a>, object: &Object, sprites: &'a Sprites) {
+    let flag = |name: &str| object.properties.get(name).map(|value| value == "true").unwrap_or(false);
+
+    match object.kind.as_str() {
+        "goomba" => {
+            world.spawn(enemy_components::walker(object.x, object.y, flag("turn-at-ledges"), Box::new(WalkerGraphicsComponent::new(&sprites.enemies))));
+        },
+        "koopa" => {
+            world.spawn(enemy_components::koopa(object.x, object.y, flag("turn-at-ledges"), Box::new(ShellGraphicsComponent::new(&sprites.enemies))));
+        },
+        "cannon" => {
+            let projectile = object.properties.get("shoots").and_then(|name| Projectile::from_name(name)).unwrap_or(Projectile::Bullet);
+            let interval = object.properties.get("interval").and_then(|interval| interval.parse().ok()).unwrap_or(3000.0);
+
+            world.spawn(projectile_components::cannon(object.x, object.y, projectile, interval, Box::new(CannonGraphicsComponent::new(&sprites.projectiles))));
+        },
+        kind => if let Some(item) = Item::from_name(kind) {
+            world.spawn(item_components::item(object.x, object.y, item, Box::new(ItemGraphicsComponent::new(&sprites.items, item))));
         }
+    }
+}
 
-        renderer.set_draw_color(Color::RGB(93, 148, 251));
-        renderer.clear();
+// What maps are drawn with, from where and at what time, for the tile
+// animations.
+struct RenderContext<'r> {
+    camera: &'r Camera,
+    tiles: &'r Texture,
+    images: &'r HashMap<String, Texture>,
+    time: f64
+}
 
-        layer.for_each_intersecting(&camera.to_rect(), |tile: &Tile, position: &Rect| {
-            let object = camera.to_relative_rect(position);
+fn render_map(map: &Map, passes: &[Pass], blocks: &Blocks, context: &RenderContext, renderer: &mut Renderer) {
+    for layer in map.layers().iter().filter(|layer| passes.contains(&layer.pass)) {
+        let view = context.camera.to_parallax_rect(layer.parallax.scroll_x, layer.parallax.scroll_y);
 
-            match *tile {
-                Tile::Background(src) | Tile::Floor(src) => renderer.copy(&world_sprites, Some(src), Some(object)).unwrap(),
-                Tile::Static(ref sprite, _) => sprite.render(lag / MS_PER_UPDATE, &mut renderer, &object),
-                _ => ()
-            }
-        });
+        match layer.data {
+            LayerData::Tiles(ref tiles) => {
+                for offset in layer.parallax.offsets(&view, 0, layer.width * map.tile_width()) {
+                    let shifted = Rect::new(view.x() - offset, view.y(), view.width(), view.height());
 
-        let player_rect = camera.to_relative_rect(&player.to_rect());
+                    tiles.for_each_intersecting(&shifted, |tile: &Tile, position: &Rect| {
+                        // bumped blocks are drawn above their place.
+                        let bump = if layer.pass == Pass::Collision {
+                            blocks.offset(position.x().div_euclid(map.tile_width() as i32), position.y().div_euclid(map.tile_height() as i32))
+                        } else {
+                            0
+                        };
 
-        player.render(elapsed, &mut renderer, &player_rect);
+                        let object = Rect::new(position.x() - shifted.x(), position.y() - shifted.y() - bump, position.width(), position.height());
 
-        renderer.present();
+                        match *tile {
+                            Tile::Defined(ref kind) => renderer.copy(context.tiles, Some(kind.frame(context.time)), Some(object)).unwrap(),
+                            Tile::Empty => ()
+                        }
+                    });
+                }
+            },
+            LayerData::Image(ref image) => {
+                let texture = &context.images[&image.path];
+                let query = texture.query();
+
+                let width = (query.width as f32 * image.scale) as u32;
+                let height = (query.height as f32 * image.scale) as u32;
+
+                for offset in layer.parallax.offsets(&view, image.x, width) {
+                    let object = Rect::new(image.x + offset - view.x(), image.y - view.y(), width, height);
+
+                    renderer.copy(texture, None, Some(object)).unwrap();
+                }
+            }
+        }
     }
 }
@@ -0,0 +1,133 @@
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+
+
+// A level of the game, and how much time there is to finish it.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Entry {
+    pub world: u32,
+    pub level: u32,
+    pub path: String,
+    pub time: u32
+}
+
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Syntax(usize, String)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Syntax(line, ref message) => write!(f, "line {}: {}", line, message)
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+
+// The levels of the game, in the order they are played.
+pub struct Manifest {
+    entries: Vec<Entry>
+}
+
+impl Manifest {
+    // A game of only the level at `path`, which is numbered by its name,
+    // like `1-2.txt`, or 1-1.
+    pub fn single(path: &str, time: u32) -> Manifest {
+        let name = Path::new(path).file_stem().and_then(|name| name.to_str()).unwrap_or("");
+        let (world, level) = parse_name(name).unwrap_or((1, 1));
+
+        Manifest {
+            entries: vec![Entry { world: world, level: level, path: path.to_string(), time: time }]
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Manifest, Error> {
+        let mut source = String::new();
+
+        File::open(path)?.read_to_string(&mut source)?;
+
+        Manifest::parse(&source)
+    }
+
+    pub fn parse(source: &str) -> Result<Manifest, Error> {
+        let mut entries = Vec::new();
+
+        for (number, line) in source.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let words = line.split_whitespace().collect::<Vec<_>>();
+
+            let entry = match (words.len(), words.first().and_then(|name| parse_name(name)), words.get(2).and_then(|time| time.parse().ok())) {
+                (3, Some((world, level)), Some(time)) => Entry { world: world, level: level, path: words[1].to_string(), time: time },
+                _ => return Err(Error::Syntax(number, "expected `<world>-<level> <path> <time>`".to_string()))
+            };
+
+            entries.push(entry);
+        }
+
+        if entries.is_empty() {
+            return Err(Error::Syntax(source.lines().count(), "missing levels".to_string()));
+        }
+
+        Ok(Manifest {
+            entries: entries
+        })
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Entry> {
+        self.entries.get(index)
+    }
+}
+
+
+fn parse_name(name: &str) -> Option<(u32, u32)> {
+    let mut numbers = name.splitn(2, '-').map(|number| number.parse().ok());
+
+    match (numbers.next(), numbers.next()) {
+        (Some(Some(world)), Some(Some(level))) => Some((world, level)),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn manifest_parse() {
+        let manifest = Manifest::parse("# world 1\n\n1-1 levels/1-1.txt 400\n1-2 levels/1-2.txt 300\n").ok().unwrap();
+
+        assert_eq!(manifest.get(1), Some(&Entry { world: 1, level: 2, path: "levels/1-2.txt".to_string(), time: 300 }));
+        assert_eq!(manifest.get(2), None);
+
+        assert_eq!(Manifest::single("levels/4-3.tmx", 400).get(0).map(|entry| (entry.world, entry.level)), Some((4, 3)));
+        assert_eq!(Manifest::single("levels/bonus.txt", 400).get(0).map(|entry| (entry.world, entry.level)), Some((1, 1)));
+
+        match Manifest::parse("1-1 levels/1-1.txt\n") {
+            Err(Error::Syntax(1, _)) => (),
+            _ => panic!("expected missing time")
+        }
+
+        match Manifest::parse("# nothing\n") {
+            Err(Error::Syntax(1, _)) => (),
+            _ => panic!("expected missing levels")
+        }
+    }
+}
@@ -0,0 +1,417 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+
+use sdl2::rect::Rect;
+
+use tile::ChunkedLayer;
+use level::Tile;
+use registry::TileType;
+use collision::Shape;
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Pass {
+    Background,
+    Collision,
+    Foreground
+}
+
+impl Pass {
+    pub fn from_name(name: &str) -> Option<Pass> {
+        match name {
+            "background" => Some(Pass::Background),
+            "collision" => Some(Pass::Collision),
+            "foreground" => Some(Pass::Foreground),
+            _ => None
+        }
+    }
+}
+
+
+// What collision sees outside of the map.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Edge {
+    Solid,
+    Empty,
+    Clamp
+}
+
+impl Edge {
+    pub fn from_name(name: &str) -> Option<Edge> {
+        match name {
+            "solid" => Some(Edge::Solid),
+            "empty" => Some(Edge::Empty),
+            "clamp" => Some(Edge::Clamp),
+            _ => None
+        }
+    }
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Edges {
+    pub left: Edge,
+    pub right: Edge,
+    pub top: Edge,
+    pub bottom: Edge
+}
+
+impl Edges {
+    pub fn new(left: Edge, right: Edge, top: Edge, bottom: Edge) -> Edges {
+        Edges {
+            left: left,
+            right: right,
+            top: top,
+            bottom: bottom
+        }
+    }
+
+    // Walls on the sides, and open sky above and pits below.
+    pub fn walls() -> Edges {
+        Edges::new(Edge::Solid, Edge::Solid, Edge::Empty, Edge::Empty)
+    }
+
+    pub fn from_names(names: &[&str]) -> Option<Edges> {
+        if names.len() != 4 {
+            return None;
+        }
+
+        match (Edge::from_name(names[0]), Edge::from_name(names[1]), Edge::from_name(names[2]), Edge::from_name(names[3])) {
+            (Some(left), Some(right), Some(top), Some(bottom)) => Some(Edges::new(left, right, top, bottom)),
+            _ => None
+        }
+    }
+}
+
+
+pub struct Object {
+    pub name: String,
+    pub kind: String,
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+    pub properties: HashMap<String, String>
+}
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Parallax {
+    pub scroll_x: f32,
+    pub scroll_y: f32,
+    pub repeat: bool
+}
+
+impl Parallax {
+    pub fn new(scroll_x: f32, scroll_y: f32, repeat: bool) -> Parallax {
+        Parallax {
+            scroll_x: scroll_x,
+            scroll_y: scroll_y,
+            repeat: repeat
+        }
+    }
+
+    pub fn none() -> Parallax {
+        Parallax::new(1.0, 1.0, false)
+    }
+
+    pub fn is_none(&self) -> bool {
+        *self == Parallax::none()
+    }
+
+    pub fn offsets(&self, view: &Rect, x: i32, width: u32) -> Vec<i32> {
+        if !self.repeat || width == 0 {
+            return vec![0];
+        }
+
+        let width = width as i32;
+
+        let first = (view.x() - x).div_euclid(width);
+        let last = (view.x() + view.width() as i32 - 1 - x).div_euclid(width);
+
+        (first..(last + 1)).map(|i| i * width).collect()
+    }
+}
+
+
+pub struct Image {
+    pub path: String,
+    pub x: i32,
+    pub y: i32,
+    pub scale: f32
+}
+
+
+pub enum LayerData {
+    Tiles(ChunkedLayer<Tile>),
+    Image(Image)
+}
+
+
+pub struct MapLayer {
+    pub pass: Pass,
+    pub width: u32,
+    pub parallax: Parallax,
+    pub data: LayerData
+}
+
+impl MapLayer {
+    pub fn tiles(&self) -> Option<&ChunkedLayer<Tile>> {
+        match self.data {
+            LayerData::Tiles(ref tiles) => Some(tiles),
+            LayerData::Image(_) => None
+        }
+    }
+}
+
+
+pub struct Map {
+    width: u32,
+    height: u32,
+    tile_width: u32,
+    tile_height: u32,
+    layers: Vec<MapLayer>,
+    // the area of the map, found again once a layer was added to it.
+    bounds: Cell<Option<Rect>>,
+    pub objects: Vec<Object>,
+    pub edges: Edges
+}
+
+impl Map {
+    pub fn new(width: u32, height: u32, tile_width: u32, tile_height: u32) -> Map {
+        Map {
+            width: width,
+            height: height,
+            tile_width: tile_width,
+            tile_height: tile_height,
+            layers: Vec::new(),
+            bounds: Cell::new(None),
+            objects: Vec::new(),
+            edges: Edges::walls()
+        }
+    }
+
+    pub fn add_layer(&mut self, pass: Pass, width: u32, parallax: Parallax) -> &mut ChunkedLayer<Tile> {
+        self.bounds.set(None);
+
+        self.layers.push(MapLayer {
+            pass: pass,
+            width: width,
+            parallax: parallax,
+            data: LayerData::Tiles(ChunkedLayer::new(self.tile_width, self.tile_height, Tile::Empty))
+        });
+
+        match self.layers.last_mut().unwrap().data {
+            LayerData::Tiles(ref mut layer) => layer,
+            LayerData::Image(_) => unreachable!()
+        }
+    }
+
+    pub fn add_image(&mut self, pass: Pass, image: Image, parallax: Parallax) {
+        self.layers.push(MapLayer {
+            pass: pass,
+            width: 0,
+            parallax: parallax,
+            data: LayerData::Image(image)
+        });
+    }
+
+    pub fn tile_width(&self) -> u32 {
+        self.tile_width
+    }
+
+    pub fn tile_height(&self) -> u32 {
+        self.tile_height
+    }
+
+    pub fn layers(&self) -> &[MapLayer] {
+        &self.layers
+    }
+
+    pub fn images(&self) -> Vec<&Image> {
+        self.layers.iter().filter_map(|layer| match layer.data {
+            LayerData::Image(ref image) => Some(image),
+            LayerData::Tiles(_) => None
+        }).collect()
+    }
+
+    pub fn shape(&self, x: i32, y: i32) -> Shape {
+        let area = self.to_rect();
+
+        let x1 = area.x() / self.tile_width as i32;
+        let y1 = area.y() / self.tile_height as i32;
+        let x2 = x1 + (area.width() / self.tile_width) as i32 - 1;
+        let y2 = y1 + (area.height() / self.tile_height) as i32 - 1;
+
+        let edge = if x < x1 {
+            Some(self.edges.left)
+        } else if x > x2 {
+            Some(self.edges.right)
+        } else if y < y1 {
+            Some(self.edges.top)
+        } else if y > y2 {
+            Some(self.edges.bottom)
+        } else {
+            None
+        };
+
+        let (x, y) = match edge {
+            Some(Edge::Solid) => return Shape::Solid,
+            Some(Edge::Empty) => return Shape::Empty,
+            Some(Edge::Clamp) | None => (x.max(x1).min(x2), y.max(y1).min(y2))
+        };
+
+        let mut shape = Shape::Empty;
+
+        for layer in self.collision_layers() {
+            match layer.get_tile(x, y).shape() {
+                Shape::Solid => return Shape::Solid,
+                Shape::Empty => (),
+                other => shape = other
+            }
+        }
+
+        shape
+    }
+
+    // Every tile that `rect` covers, including the ones outside of the map,
+    // where `shape` follows the `edges`.
+    pub fn find_intersecting(&self, rect: &Rect) -> Option<Rect> {
+        let x1 = rect.x().div_euclid(self.tile_width as i32);
+        let y1 = rect.y().div_euclid(self.tile_height as i32);
+
+        let x2 = (rect.x() + rect.width() as i32 - 1).div_euclid(self.tile_width as i32);
+        let y2 = (rect.y() + rect.height() as i32 - 1).div_euclid(self.tile_height as i32);
+
+        Some(Rect::new(x1, y1, (x2 - x1 + 1) as u32, (y2 - y1 + 1) as u32))
+    }
+
+    // The types of all tiles in collision layers that overlap `rect`.
+    pub fn types_at(&self, rect: &Rect) -> Vec<&TileType> {
+        self.tiles_at(rect).into_iter().map(|(_, kind)| kind).collect()
+    }
+
+    // The positions and types of all tiles in collision layers that overlap
+    // `rect`.
+    pub fn tiles_at(&self, rect: &Rect) -> Vec<((i32, i32), &TileType)> {
+        let mut tiles = Vec::new();
+
+        for layer in self.collision_layers() {
+            if let Some(intersect) = layer.find_intersecting(rect) {
+                for y in intersect.y()..(intersect.y() + intersect.height() as i32) {
+                    for x in intersect.x()..(intersect.x() + intersect.width() as i32) {
+                        if let Some(kind) = layer.get_tile(x, y).kind() {
+                            tiles.push(((x, y), kind));
+                        }
+                    }
+                }
+            }
+        }
+
+        tiles
+    }
+
+    // The type of the first tile at `x`, `y` in a collision layer.
+    pub fn kind_at(&self, x: i32, y: i32) -> Option<&TileType> {
+        self.collision_layers().filter_map(|layer| layer.get_tile(x, y).kind()).next()
+    }
+
+    // Replaces the first tile at `x`, `y` in a collision layer, returns false
+    // if there is none.
+    pub fn replace_tile(&mut self, x: i32, y: i32, tile: Tile) -> bool {
+        for layer in self.layers.iter_mut().filter(|layer| layer.pass == Pass::Collision) {
+            if let LayerData::Tiles(ref mut tiles) = layer.data {
+                if tiles.get_tile(x, y).kind().is_some() {
+                    tiles.set_tile(x, y, tile);
+
+                    return true;
+                }
+            }
+        }
+
+        false
+    }
+
+    fn collision_layers<'m>(&'m self) -> impl Iterator<Item=&'m ChunkedLayer<Tile>> + 'm {
+        self.layers.iter().filter(|layer| layer.pass == Pass::Collision).filter_map(|layer| layer.tiles())
+    }
+
+    // The area of the map, which grows when tiles are set outside of it.
+    pub fn to_rect(&self) -> Rect {
+        if let Some(bounds) = self.bounds.get() {
+            return bounds;
+        }
+
+        let area = Rect::new(0, 0, self.width * self.tile_width, self.height * self.tile_height);
+        let bounds = self.collision_layers().filter_map(|layer| layer.to_rect()).fold(area, |area, bounds| area.union(bounds));
+
+        self.bounds.set(Some(bounds));
+
+        bounds
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::rc::Rc;
+
+    use sdl2::rect::Rect;
+
+    use super::*;
+
+    #[test]
+    fn parallax_offsets() {
+        let view = Rect::new(10, 0, 20, 10);
+
+        assert_eq!(Parallax::none().offsets(&view, 0, 8), vec![0]);
+
+        let parallax = Parallax::new(0.5, 1.0, true);
+
+        assert_eq!(parallax.offsets(&view, 0, 8), vec![8, 16, 24]);
+        assert_eq!(parallax.offsets(&view, 0, 10), vec![10, 20]);
+        assert_eq!(parallax.offsets(&view, 12, 10), vec![-10, 0, 10]);
+        assert_eq!(parallax.offsets(&Rect::new(-5, 0, 5, 10), 0, 10), vec![-10]);
+    }
+
+    #[test]
+    fn map_shape_edges() {
+        let mut map = Map::new(2, 2, 10, 10);
+
+        let mut floor = TileType::new("floor", Rect::new(0, 0, 16, 16));
+
+        floor.solid = true;
+
+        map.add_layer(Pass::Collision, 2, Parallax::none()).set_tile(0, 1, Tile::Defined(Rc::new(floor)));
+
+        assert_eq!(map.find_intersecting(&Rect::new(-15, 15, 10, 10)), Some(Rect::new(-2, 1, 2, 2)));
+
+        assert_eq!(map.shape(-1, 0), Shape::Solid);
+        assert_eq!(map.shape(2, 1), Shape::Solid);
+        assert_eq!(map.shape(0, -1), Shape::Empty);
+        assert_eq!(map.shape(0, 2), Shape::Empty);
+
+        map.edges = Edges::new(Edge::Empty, Edge::Clamp, Edge::Solid, Edge::Clamp);
+
+        assert_eq!(map.shape(-1, 1), Shape::Empty);
+        assert_eq!(map.shape(5, 1), Shape::Empty);
+        assert_eq!(map.shape(0, -5), Shape::Solid);
+        assert_eq!(map.shape(0, 5), Shape::Solid);
+        assert_eq!(map.shape(1, 5), Shape::Empty);
+    }
+
+    #[test]
+    fn map_to_rect() {
+        let mut map = Map::new(4, 2, 10, 10);
+
+        map.add_layer(Pass::Background, 4, Parallax::none()).set_tile(-10, 0, Tile::Empty);
+
+        assert_eq!(map.to_rect(), Rect::new(0, 0, 40, 20));
+
+        let ground = map.add_layer(Pass::Collision, 4, Parallax::none());
+
+        ground.set_tile(0, 1, Tile::Empty);
+        ground.set_tile(99, 2, Tile::Empty);
+
+        assert_eq!(map.to_rect(), Rect::new(0, 0, 1000, 30));
+    }
+}
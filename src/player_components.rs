@@ -1,79 +1,885 @@
-use std::cell::{Cell, RefCell};
-
 use sdl2::rect::Rect;
 use sdl2::render::{Renderer, Texture};
 
 use sprite::{Sprite, StaticSprite, AnimatedSprite};
-use game_object::GameObject;
-use component::{Updatable, Renderable};
+use game_object::{GameObject, Pose, Power};
+use component::{TICK, GRAVITY, TERMINAL_SPEED, Updatable, Renderable, Input, Context, Event, Touch, Velocity, Health, Actor, Look};
+use contact::{self, Collider, Side};
+use item_components::Item;
+use projectile_components::Projectile;
+use goal_components::SLIDE_SPEED;
+use map::Map;
+use collision;
+
+
+const SPEED_X : f32 = 4.0;
+const THRESHOLD_X : f32 = 0.2;
+
+const ACCELERATION_X_START : f32 = 0.02;
+const ACCELERATION_X_STOP : f32 = 0.15;
+const ACCELERATION_X_CHANGE : f32 = 0.06;
+
+const JUMP_SPEED : f32 = 8.0;
+const JUMP_CUT_SPEED : f32 = 4.0;
+const STOMP_SPEED : f32 = 6.0;
+
+const CLIMB_SPEED : f32 = 2.0;
+const SWIM_SPEED : f32 = 2.0;
+
+// the height of the small player, and of the big one standing up.
+const SMALL_HEIGHT : u32 = 32;
+const BIG_HEIGHT : u32 = 64;
+
+// how long growing and shrinking take, and how long the player can not be
+// hurt again after shrinking.
+const GROW_TIME : f64 = 800.0;
+const INVINCIBLE_TIME : f64 = 2000.0;
+
+// how long a star lets the player knock out the enemies it touches.
+const STAR_TIME : f64 = 10000.0;
+
+// how often the player flashes while it grows, shrinks or blinks.
+const FLASH_TIME : f64 = 80.0;
+
+// how long the player hangs in the air when it dies, before it jumps up and
+// falls off the map.
+const DEATH_TIME : f64 = 500.0;
+const DEATH_SPEED : f32 = 10.0;
+
+
+pub fn player_collider() -> Collider {
+    Collider::new(contact::PLAYER, contact::ENEMY | contact::PICKUP | contact::KICKABLE)
+}
+
+// enemies are knocked out by the shining player as if it was a projectile.
+fn star_collider() -> Collider {
+    Collider::new(contact::PLAYER | contact::PROJECTILE, contact::ENEMY | contact::PICKUP | contact::KICKABLE)
+}
+
+
+pub fn player<'a>(x: f32, y: f32, graphics: Box<dyn Renderable + 'a>) -> GameObject<'a> {
+    GameObject::new(x, y)
+        .with_velocity(Velocity::new(0.0, 0.0))
+        .with_collider(player_collider())
+        .with_graphics(graphics)
+        .with_health(Health::new(GROW_TIME + INVINCIBLE_TIME))
+        .with_ai(Box::new(PlayerPhysicsComponent::new()))
+}
 
 
+// Moves the player as the input says. It is hurt and killed through its
+// health, and shrinks for the first part of the time it is invincible
+// afterwards, then blinks for the rest. A star makes it shine for a while,
+// during which nothing that it touches hurts it.
 pub struct PlayerPhysicsComponent {
-    pub dx: f32,
-    pub dy: f32,
-    pub gravity: f32,
+    time: f64,
+    star: f64,
+    held: Option<(f32, f32)>,
+    above: bool
 }
 
 impl PlayerPhysicsComponent {
     pub fn new() -> PlayerPhysicsComponent {
         PlayerPhysicsComponent {
-            dx: 0.0,
-            dy: 0.0,
-            gravity: 0.3,
+            time: 0.0,
+            star: 0.0,
+            held: None,
+            above: true
+        }
+    }
+
+    // Counts down the star, and stops shining once it runs out.
+    fn shine(&mut self, actor: &mut Actor, dt: f64) {
+        if self.star <= 0.0 {
+            return;
+        }
+
+        self.star -= dt;
+
+        if self.star > 0.0 {
+            return;
+        }
+
+        *actor.collider = Some(player_collider());
+
+        if *actor.pose == Pose::Shining {
+            *actor.pose = Pose::Normal;
         }
     }
+
+    // Fits the body to the power of the player, and crouches the big player
+    // while down is held on the ground, keeping its feet in place. Only stands
+    // up where there is room above, and stays crouched otherwise.
+    fn resize(&mut self, actor: &mut Actor, map: &Map, input: &Input, power: Power) {
+        let crouch = actor.velocity.on_ground && input.down;
+        let height = if power == Power::Small || crouch { SMALL_HEIGHT } else { BIG_HEIGHT };
+        let transform = &mut *actor.transform;
+
+        if height > transform.h {
+            let result = collision::move_and_collide(map, &transform.to_body(), 0.0, -((height - transform.h) as f32), false);
+
+            if result.top {
+                return;
+            }
+        }
+
+        transform.y += transform.h as f32 - height as f32;
+        transform.h = height;
+    }
+
+    // Stays where it is while it grows or shrinks, and carries on as it was
+    // afterwards.
+    fn freeze(&mut self, velocity: &mut Velocity) {
+        if self.held.is_none() {
+            self.held = Some((velocity.dx, velocity.dy));
+        }
+
+        *velocity = Velocity { gravity: 0.0, solid: false, on_ground: velocity.on_ground, ..Velocity::new(0.0, 0.0) };
+    }
+
+    // Hangs in the air for a while, then jumps up and falls through the map,
+    // and has died once it falls off the bottom.
+    fn die(&mut self, actor: &mut Actor, context: &Context) -> Vec<Event> {
+        if self.time > 0.0 {
+            self.time -= context.dt;
+
+            if self.time <= 0.0 {
+                actor.velocity.dy = -DEATH_SPEED;
+                actor.velocity.gravity = GRAVITY;
+            }
+        }
+
+        let below = actor.transform.y > context.map.to_rect().bottom() as f32;
+        let died = self.above && below;
+
+        self.above = !below;
+
+        if died {
+            vec![Event::Died]
+        } else {
+            Vec::new()
+        }
+    }
+
+    fn walk(&mut self, actor: &mut Actor, map: &Map, input: &Input, scale: f32) {
+        let on_ground = actor.velocity.on_ground;
+        let dx = actor.velocity.dx;
+
+        actor.velocity.dx = if input.right && (dx >= 0.0 || on_ground) {
+            let a = if dx > 0.0 {
+                ACCELERATION_X_START
+            } else {
+                ACCELERATION_X_CHANGE
+            } * scale;
+
+            a * SPEED_X + (1.0 - a) * dx
+        } else if input.left && (dx <= 0.0 || on_ground) {
+            let a = if dx < 0.0 {
+                ACCELERATION_X_START
+            } else {
+                ACCELERATION_X_CHANGE
+            } * scale;
+
+            a * -SPEED_X + (1.0 - a) * dx
+        } else if on_ground {
+            let friction = map.types_at(&collision::below(&actor.transform.to_rect())).iter().map(|kind| kind.friction).fold(0.0, f32::max);
+            let dx = dx * (1.0 - (ACCELERATION_X_STOP * friction * scale).min(1.0));
+
+            if dx.abs() <= THRESHOLD_X { 0.0 } else { dx }
+        } else {
+            dx
+        };
+    }
+
+    fn jump(&mut self, velocity: &mut Velocity, swimming: bool, input: &Input, events: &mut Vec<Event>) {
+        if swimming && input.jump {
+            velocity.dy = -SWIM_SPEED * 2.0;
+        } else if velocity.on_ground && input.jump {
+            if input.down {
+                velocity.drop_through = true;
+            } else {
+                velocity.dy = -JUMP_SPEED;
+                velocity.on_ground = false;
+
+                events.push(Event::Jumped);
+            }
+        }
+
+        if input.jump_released && velocity.dy < -JUMP_CUT_SPEED {
+            velocity.dy = -JUMP_CUT_SPEED;
+        }
+    }
+
+    // Climbs at a constant speed, sinks slowly in liquids and falls
+    // everywhere else.
+    fn fall(&mut self, velocity: &mut Velocity, climbing: bool, swimming: bool, input: &Input) {
+        velocity.gravity = GRAVITY;
+        velocity.terminal = TERMINAL_SPEED;
+
+        if climbing {
+            velocity.gravity = 0.0;
+            velocity.dy = if input.up { -CLIMB_SPEED } else if input.down { CLIMB_SPEED } else { 0.0 };
+        } else if swimming {
+            velocity.gravity = GRAVITY / 4.0;
+            velocity.terminal = SWIM_SPEED;
+        }
+
+        velocity.dy = velocity.dy.max(-TERMINAL_SPEED);
+    }
 }
 
 impl Updatable for PlayerPhysicsComponent {
-    fn update(&self, _: &GameObject) {
+    fn update(&mut self, actor: &mut Actor, context: &Context) -> Vec<Event> {
+        let mut events = Vec::new();
+
+        let scale = (context.dt / TICK) as f32;
+
+        match *actor.pose {
+            Pose::Dying => return self.die(actor, context),
+            // slides down the pole at the end of the level, until it lands.
+            Pose::Sliding => {
+                *actor.velocity = Velocity { dy: SLIDE_SPEED, gravity: 0.0, on_ground: actor.velocity.on_ground, ..Velocity::new(0.0, 0.0) };
+
+                return events;
+            },
+            // goes into a pipe or a door the way the game sets it moving,
+            // through whatever is in the way.
+            Pose::Entering => {
+                actor.velocity.gravity = 0.0;
+                actor.velocity.solid = false;
+
+                return events;
+            },
+            _ => ()
+        }
+
+        let (power, invincible) = match actor.health {
+            Some(ref health) => (health.power, health.invincible),
+            None => (Power::Small, 0.0)
+        };
+
+        // dies in hazards and when it falls off the map.
+        let fallen = actor.transform.y > context.map.to_rect().bottom() as f32;
+        let hazard = context.map.types_at(&actor.transform.to_rect()).iter().chain(context.map.types_at(&collision::below(&actor.transform.to_rect())).iter()).any(|kind| kind.hazard);
+
+        self.above = !fallen;
+
+        if fallen || hazard {
+            if let Some(ref mut health) = actor.health {
+                health.kill();
+            }
+
+            return events;
+        }
+
+        self.shine(actor, context.dt);
+        self.resize(actor, context.map, &context.input, power);
+
+        match *actor.pose {
+            Pose::Growing if invincible > 0.0 => {
+                self.freeze(actor.velocity);
+
+                return events;
+            },
+            Pose::Shrinking if invincible > INVINCIBLE_TIME => {
+                self.freeze(actor.velocity);
+
+                return events;
+            },
+            Pose::Growing => *actor.pose = Pose::Normal,
+            Pose::Shrinking => *actor.pose = Pose::Blinking,
+            Pose::Blinking if invincible <= 0.0 => *actor.pose = Pose::Normal,
+            Pose::Normal if self.star > 0.0 => *actor.pose = Pose::Shining,
+            _ => ()
+        }
+
+        if let Some((dx, dy)) = self.held.take() {
+            actor.velocity.dx = dx;
+            actor.velocity.dy = dy;
+        }
+
+        actor.velocity.solid = true;
+
+        let (climbing, swimming) = {
+            let touching = context.map.types_at(&actor.transform.to_rect());
+
+            (touching.iter().any(|kind| kind.climbable), touching.iter().any(|kind| kind.liquid))
+        };
+
+        // can not walk while crouching.
+        let mut input = context.input;
+
+        if actor.velocity.on_ground && input.down && power != Power::Small {
+            input.left = false;
+            input.right = false;
+        }
+
+        self.walk(actor, context.map, &input, scale);
+
+        // only turns around on the ground.
+        if actor.velocity.on_ground && actor.velocity.dx != 0.0 {
+            actor.transform.facing = actor.velocity.dx.signum();
+        }
+
+        if power == Power::Fire && input.action_pressed {
+            events.push(Event::Shot(Projectile::Fireball));
+        }
+
+        self.jump(actor.velocity, swimming, &input, &mut events);
+        self.fall(actor.velocity, climbing, swimming, &input);
+
+        events
+    }
+
+    // Bounces off enemies that it lands on, and is hit by the others and by
+    // shots, unless it shines.
+    fn touch(&mut self, actor: &mut Actor, touch: &Touch, _: &Context) -> Vec<Event> {
+        if touch.layer & (contact::ENEMY | contact::SHOT) == 0 || *actor.pose == Pose::Dying || self.star > 0.0 {
+            return Vec::new();
+        }
+
+        if touch.side == Side::Top && touch.layer & contact::ENEMY != 0 {
+            actor.velocity.dy = -STOMP_SPEED;
+            actor.velocity.on_ground = false;
+        } else if let Some(ref mut health) = actor.health {
+            health.hit();
+        }
+
+        Vec::new()
+    }
+
+    // Mushrooms make the small player big, and flowers give it fire. It grows
+    // while it can not be hurt. Stars make it shine.
+    fn collect(&mut self, actor: &mut Actor, item: Item) {
+        if item == Item::Star {
+            self.star = STAR_TIME;
+
+            *actor.collider = Some(star_collider());
+
+            if *actor.pose == Pose::Normal {
+                *actor.pose = Pose::Shining;
+            }
+
+            return;
+        }
+
+        let health = match actor.health {
+            Some(ref mut health) => health,
+            None => return
+        };
+
+        let power = match (item, health.power) {
+            (Item::Mushroom, Power::Small) => Power::Big,
+            (Item::Flower, Power::Small) | (Item::Flower, Power::Big) => Power::Fire,
+            _ => return
+        };
+
+        health.power = power;
+        health.invincible = GROW_TIME;
+
+        *actor.pose = Pose::Growing;
+    }
+
+    fn hurt(&mut self, actor: &mut Actor) {
+        *actor.pose = Pose::Shrinking;
+    }
+
+    // Stops the player and starts dying, which it does small and without
+    // touching anything.
+    fn kill(&mut self, actor: &mut Actor) {
+        if *actor.pose == Pose::Dying {
+            return;
+        }
+
+        actor.transform.y += actor.transform.h as f32 - SMALL_HEIGHT as f32;
+        actor.transform.h = SMALL_HEIGHT;
+
+        if let Some(ref mut health) = actor.health {
+            health.power = Power::Small;
+        }
+
+        *actor.pose = Pose::Dying;
+        *actor.collider = None;
+        *actor.velocity = Velocity { gravity: 0.0, solid: false, ..Velocity::new(0.0, 0.0) };
+
+        self.time = DEATH_TIME;
+        self.star = 0.0;
+        self.held = None;
+    }
+}
+
+
+// The sprites for one power of the player, which has no crouching sprite
+// when it is small.
+struct SpriteSet<'a> {
+    height: u32,
+    standing: StaticSprite<'a>,
+    running: AnimatedSprite<'a>,
+    jumping: StaticSprite<'a>,
+    climbing: StaticSprite<'a>,
+    crouching: Option<StaticSprite<'a>>
+}
+
+impl<'a> SpriteSet<'a> {
+    fn small(texture: &'a Texture) -> SpriteSet<'a> {
+        SpriteSet {
+            height: SMALL_HEIGHT,
+            standing: StaticSprite::new(texture, 80, 32),
+            running: AnimatedSprite::new(texture, 96, 32, 3, 10.0),
+            jumping: StaticSprite::new(texture, 160, 32),
+            climbing: StaticSprite::new(texture, 192, 32),
+            crouching: None
+        }
+    }
+
+    // big sprites are two cells high, from the row at `y`.
+    fn big(texture: &'a Texture, y: i32) -> SpriteSet<'a> {
+        SpriteSet {
+            height: BIG_HEIGHT,
+            standing: StaticSprite::new(texture, 80, y).with_size(16, 32),
+            running: AnimatedSprite::new(texture, 96, y, 3, 10.0).with_size(16, 32),
+            jumping: StaticSprite::new(texture, 160, y).with_size(16, 32),
+            climbing: StaticSprite::new(texture, 192, y).with_size(16, 32),
+            crouching: Some(StaticSprite::new(texture, 176, y).with_size(16, 32))
+        }
     }
 }
 
 
 pub struct PlayerGraphicsComponent<'a> {
-    flip_horizontal: Cell<bool>,
-    sprite_standing: RefCell<StaticSprite<'a>>,
-    sprite_running: RefCell<AnimatedSprite<'a>>,
-    sprite_jumping: RefCell<StaticSprite<'a>>
+    time: f64,
+    dying: StaticSprite<'a>,
+    small: SpriteSet<'a>,
+    big: SpriteSet<'a>,
+    fire: SpriteSet<'a>
 }
 
 impl<'a> PlayerGraphicsComponent<'a> {
     pub fn new(texture: &'a Texture) -> PlayerGraphicsComponent<'a> {
         PlayerGraphicsComponent {
-            flip_horizontal: Cell::new(false),
-            sprite_standing: RefCell::new(StaticSprite::new(texture, 80, 32)),
-            sprite_running: RefCell::new(AnimatedSprite::new(texture, 96, 32, 3, 10.0)),
-            sprite_jumping: RefCell::new(StaticSprite::new(texture, 160, 32))
+            time: 0.0,
+            dying: StaticSprite::new(texture, 176, 32),
+            small: SpriteSet::small(texture),
+            big: SpriteSet::big(texture, 0),
+            fire: SpriteSet::big(texture, 144)
         }
     }
 }
 
 impl<'a> Renderable for PlayerGraphicsComponent<'a> {
-    fn render(&self, object: &GameObject, elapsed: f64, renderer: &mut Renderer, destination: &Rect) {
-        if !object.on_ground {
-            let mut sprite = self.sprite_jumping.borrow_mut();
+    fn render(&mut self, look: &Look, elapsed: f64, renderer: &mut Renderer, destination: &Rect) {
+        self.time += elapsed;
 
-            sprite.flip_horizontal = self.flip_horizontal.get();
+        if look.pose == Pose::Dying {
+            self.dying.render(elapsed, renderer, destination);
 
-            sprite.render(elapsed, renderer, destination);
-        } else if object.dx == 0.0 {
-            let mut sprite = self.sprite_standing.borrow_mut();
+            return;
+        }
 
-            sprite.flip_horizontal = self.flip_horizontal.get();
+        let flip_horizontal = look.facing < 0.0;
+        let flash = (self.time / FLASH_TIME) as u32 % 2 == 1;
 
-            sprite.render(elapsed, renderer, destination);
-        } else {
-            let mut sprite = self.sprite_running.borrow_mut();
-
-            if object.dx < 0.0 {
-                sprite.flip_horizontal = true;
-                self.flip_horizontal.set(true);
-            } else if object.dx > 0.0 {
-                sprite.flip_horizontal = false;
-                self.flip_horizontal.set(false);
-            }
+        // flashes between the power it had and the one it gets.
+        let power = match (look.pose, look.power) {
+            (Pose::Growing, Power::Fire) if flash => Power::Big,
+            (Pose::Growing, _) if flash => Power::Small,
+            (Pose::Shrinking, _) if flash => Power::Big,
+            (Pose::Blinking, _) | (Pose::Shining, _) if flash => return,
+            (_, power) => power
+        };
 
-            sprite.render(elapsed, renderer, destination);
+        let set = match power {
+            Power::Small => &mut self.small,
+            Power::Big => &mut self.big,
+            Power::Fire => &mut self.fire
         };
+
+        // the sprites stand on the bottom of the body, whatever its height.
+        let destination = Rect::new(destination.x(), destination.bottom() - set.height as i32, destination.width(), set.height);
+
+        if look.pose == Pose::Sliding {
+            set.climbing.flip_horizontal = flip_horizontal;
+            set.climbing.render(elapsed, renderer, &destination);
+
+            return;
+        }
+
+        match set.crouching {
+            Some(ref mut crouching) if look.h < set.height => {
+                crouching.flip_horizontal = flip_horizontal;
+                crouching.render(elapsed, renderer, &destination);
+            },
+            _ if !look.on_ground => {
+                set.jumping.flip_horizontal = flip_horizontal;
+                set.jumping.render(elapsed, renderer, &destination);
+            },
+            _ if look.dx == 0.0 => {
+                set.standing.flip_horizontal = flip_horizontal;
+                set.standing.render(elapsed, renderer, &destination);
+            },
+            _ => {
+                set.running.flip_horizontal = flip_horizontal;
+                set.running.render(elapsed, renderer, &destination);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::rect::Rect;
+
+    use world::{World, Entity};
+    use item_components;
+    use enemy_components;
+    use test_util::{self, Invisible};
+
+    use super::*;
+
+    fn map() -> Map {
+        // a floor at the bottom of a 20x10 map.
+        test_util::map(&[
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "####################"
+        ])
+    }
+
+    fn spawn(world: &mut World, x: f32, y: f32) -> Entity {
+        let player = world.spawn(player(x, y, Box::new(Invisible)));
+
+        world.maintain();
+
+        player
+    }
+
+    fn velocity(world: &World, player: Entity) -> (f32, f32) {
+        world.velocity(player).map(|velocity| (velocity.dx, velocity.dy)).unwrap()
+    }
+
+    #[test]
+    fn player_physics_walk() {
+        let map = map();
+        let mut world = World::new(32);
+        let player = spawn(&mut world, 64.0, 256.0);
+
+        let mut context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+
+        assert_eq!(world.update(&context), vec![(player, Event::Landed)]);
+        assert!(world.velocity(player).unwrap().on_ground);
+        assert_eq!((world.transform(player).unwrap().y, velocity(&world, player)), (256.0, (0.0, 0.0)));
+
+        context.input.right = true;
+
+        for _ in 0..100 {
+            world.update(&context);
+        }
+
+        let (dx, _) = velocity(&world, player);
+
+        assert!(dx > 2.0 && dx < SPEED_X);
+        assert!(world.transform(player).unwrap().x > 200.0);
+
+        context.input.right = false;
+
+        for _ in 0..100 {
+            world.update(&context);
+        }
+
+        assert_eq!(velocity(&world, player), (0.0, 0.0));
+    }
+
+    #[test]
+    fn player_physics_jump() {
+        let map = map();
+        let mut world = World::new(32);
+        let player = spawn(&mut world, 64.0, 256.0);
+
+        let mut context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+
+        world.update(&context);
+
+        context.input.jump = true;
+
+        assert_eq!(world.update(&context), vec![(player, Event::Jumped)]);
+        assert!(!world.velocity(player).unwrap().on_ground);
+        assert_eq!(velocity(&world, player), (0.0, -JUMP_SPEED + GRAVITY));
+
+        context.input.jump = false;
+        context.input.jump_released = true;
+
+        world.update(&context);
+
+        assert_eq!(velocity(&world, player), (0.0, -JUMP_CUT_SPEED + GRAVITY));
+
+        // falls no faster than terminal speed, from high above the map.
+        let player = spawn(&mut world, 64.0, -2000.0);
+
+        context.input = Input::default();
+
+        for _ in 0..100 {
+            world.update(&context);
+        }
+
+        assert_eq!(velocity(&world, player), (0.0, TERMINAL_SPEED));
+
+        // moves twice as far in a tick twice as long.
+        let mut world = World::new(32);
+        let player = world.spawn(super::player(64.0, 0.0, Box::new(Invisible)).with_velocity(Velocity::new(0.0, 2.0)));
+
+        world.update(&Context { map: &map, input: Input::default(), player: None, dt: TICK * 2.0 });
+
+        assert_eq!(world.transform(player).unwrap().y, 2.0 * (2.0 + 2.0 * GRAVITY));
+    }
+
+    #[test]
+    fn player_physics_bump() {
+        let map = test_util::map(&[
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "..##................",
+            "....................",
+            "....................",
+            "....................",
+            "####################"
+        ]);
+
+        // bumps the one of the two blocks above that is closest to its middle.
+        let mut world = World::new(32);
+        let player = spawn(&mut world, 70.0, 256.0);
+        let mut context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+
+        world.update(&context);
+
+        context.input.jump = true;
+
+        let mut events = Vec::new();
+
+        for _ in 0..50 {
+            events.extend(world.update(&context));
+
+            context.input.jump = false;
+        }
+
+        assert_eq!(events, vec![(player, Event::Jumped), (player, Event::Bumped(2, 5)), (player, Event::Landed)]);
+    }
+
+    #[test]
+    fn player_power_ups() {
+        let map = test_util::map(&[
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            "....................",
+            ".....#..............",
+            "....................",
+            "####################"
+        ]);
+
+        let mut context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+        let mut world = World::new(32);
+        let player = spawn(&mut world, 64.0, 256.0);
+
+        world.update(&context);
+
+        // grows upwards, and waits until it has grown.
+        let mushroom = world.spawn(item_components::item(64.0, 256.0, Item::Mushroom, Box::new(Invisible)));
+
+        assert!(world.update(&context).contains(&(mushroom, Event::Collected(Item::Mushroom))));
+
+        world.update(&context);
+
+        assert_eq!((world.health(player).unwrap().power, world.pose(player)), (Power::Big, Some(Pose::Growing)));
+        assert_eq!(world.transform(player).map(|transform| (transform.y, transform.h)), Some((224.0, BIG_HEIGHT)));
+
+        for _ in 0..((GROW_TIME / TICK) as usize) {
+            world.update(&context);
+        }
+
+        assert_eq!(world.pose(player), Some(Pose::Normal));
+
+        // crouches, and only stands up where there is room.
+        context.input.down = true;
+
+        world.update(&context);
+
+        assert_eq!(world.transform(player).map(|transform| (transform.y, transform.h)), Some((256.0, SMALL_HEIGHT)));
+
+        world.transform_mut(player).unwrap().x = 160.0;
+        context.input.down = false;
+
+        world.update(&context);
+
+        assert_eq!(world.transform(player).map(|transform| (transform.y, transform.h)), Some((256.0, SMALL_HEIGHT)));
+
+        world.transform_mut(player).unwrap().x = 64.0;
+
+        world.update(&context);
+
+        assert_eq!(world.transform(player).map(|transform| (transform.y, transform.h)), Some((224.0, BIG_HEIGHT)));
+
+        // shrinks when hurt, and can not be hurt again for a while.
+        let touch = Touch { entity: None, side: Side::Left, layer: contact::ENEMY, rect: Rect::new(32, 256, 32, 32) };
+
+        assert_eq!(world.touch(player, &touch, &context), vec![(player, Event::Hurt)]);
+        assert_eq!((world.health(player).unwrap().power, world.pose(player)), (Power::Small, Some(Pose::Shrinking)));
+
+        for _ in 0..((GROW_TIME / TICK) as usize + 1) {
+            world.update(&context);
+        }
+
+        assert_eq!((world.pose(player), world.transform(player).unwrap().h), (Some(Pose::Blinking), SMALL_HEIGHT));
+        assert!(world.touch(player, &touch, &context).is_empty());
+
+        for _ in 0..((INVINCIBLE_TIME / TICK) as usize) {
+            world.update(&context);
+        }
+
+        assert_eq!(world.touch(player, &touch, &context), vec![(player, Event::Killed)]);
+    }
+
+    #[test]
+    fn player_star() {
+        let map = map();
+        let context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+        let mut world = World::new(32);
+        let player = spawn(&mut world, 64.0, 256.0);
+
+        world.update(&context);
+
+        let star = world.spawn(item_components::item(64.0, 256.0, Item::Star, Box::new(Invisible)));
+
+        assert!(world.update(&context).contains(&(star, Event::Collected(Item::Star))));
+        assert_eq!(world.pose(player), Some(Pose::Shining));
+
+        // knocks out the enemies it touches, and is not hurt by them.
+        let walker = world.spawn(enemy_components::walker(100.0, 256.0, false, Box::new(Invisible)));
+        let mut events = Vec::new();
+
+        for _ in 0..10 {
+            events.extend(world.update(&context));
+        }
+
+        assert!(events.contains(&(walker, Event::Killed)));
+        assert_eq!((world.pose(walker), world.pose(player)), (Some(Pose::KnockedOut), Some(Pose::Shining)));
+
+        // stops shining once the star runs out.
+        for _ in 0..((STAR_TIME / TICK) as usize) {
+            world.update(&context);
+        }
+
+        let touch = Touch { entity: None, side: Side::Left, layer: contact::ENEMY, rect: Rect::new(32, 256, 32, 32) };
+
+        assert_eq!(world.pose(player), Some(Pose::Normal));
+        assert_eq!(world.touch(player, &touch, &context), vec![(player, Event::Killed)]);
+    }
+
+    #[test]
+    fn player_death() {
+        let map = map();
+        let context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+
+        // dies when it falls off the map, and hangs in the air for a while.
+        let mut world = World::new(32);
+        let player = spawn(&mut world, 64.0, 400.0);
+
+        world.health_mut(player).unwrap().power = Power::Big;
+        world.transform_mut(player).unwrap().h = BIG_HEIGHT;
+
+        assert_eq!(world.update(&context), vec![(player, Event::Killed)]);
+        assert_eq!((world.health(player).unwrap().power, world.pose(player), world.transform(player).unwrap().h), (Power::Small, Some(Pose::Dying), SMALL_HEIGHT));
+
+        let y = world.transform(player).unwrap().y;
+
+        for _ in 1..((DEATH_TIME / TICK) as usize) {
+            assert!(world.update(&context).is_empty());
+        }
+
+        assert_eq!(world.transform(player).unwrap().y, y);
+
+        // then jumps up, and has died once it falls off the bottom.
+        let mut events = Vec::new();
+
+        for _ in 0..200 {
+            events.extend(world.update(&context));
+        }
+
+        assert_eq!(events, vec![(player, Event::Died)]);
+
+        // time running out kills it as well, but only once.
+        let mut world = World::new(32);
+        let player = spawn(&mut world, 64.0, 256.0);
+
+        world.update(&context);
+        world.health_mut(player).unwrap().kill();
+
+        assert_eq!(world.update(&context), vec![(player, Event::Killed)]);
+        assert_eq!(world.pose(player), Some(Pose::Dying));
+
+        for _ in 1..((DEATH_TIME / TICK) as usize) {
+            world.update(&context);
+        }
+
+        world.health_mut(player).unwrap().kill();
+        world.update(&context);
+        world.update(&context);
+
+        assert!(world.transform(player).unwrap().y < 256.0);
+    }
+
+    #[test]
+    fn player_slide() {
+        let map = map();
+        let mut context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+
+        // slides straight down the pole, whatever is held, until it lands.
+        let mut world = World::new(32);
+        let player = spawn(&mut world, 64.0, 0.0);
+
+        world.set_pose(player, Pose::Sliding);
+        context.input.right = true;
+
+        world.update(&context);
+
+        assert_eq!(world.transform(player).map(|transform| (transform.x, transform.y)), Some((64.0, SLIDE_SPEED)));
+
+        for _ in 0..100 {
+            world.update(&context);
+        }
+
+        assert!(world.velocity(player).unwrap().on_ground);
+        assert_eq!(world.transform(player).unwrap().y, 256.0);
+    }
+
+    #[test]
+    fn player_enter() {
+        let map = map();
+        let context = Context { map: &map, input: Input::default(), player: None, dt: TICK };
+
+        // sinks into the floor as it is set moving, without falling.
+        let mut world = World::new(32);
+        let player = spawn(&mut world, 64.0, 256.0);
+
+        world.set_pose(player, Pose::Entering);
+        world.velocity_mut(player).unwrap().dy = 1.0;
+
+        for _ in 0..40 {
+            world.update(&context);
+        }
+
+        assert_eq!(world.transform(player).map(|transform| (transform.x, transform.y)), Some((64.0, 296.0)));
     }
 }
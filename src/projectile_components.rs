@@ -0,0 +1,401 @@
+use sdl2::rect::Rect;
+use sdl2::render::{Renderer, Texture};
+
+use sprite::{Sprite, StaticSprite, AnimatedSprite};
+use game_object::{GameObject, Pose};
+use collision::Grid;
+use component::{Updatable, Renderable, Context, Event, Touch, Transform, Velocity, Actor, Look};
+use contact::{self, Collider, Side};
+use world::{World, Entity};
+use enemy_components;
+
+
+const FIREBALL_SPEED : f32 = 6.0;
+const FIREBALL_BOUNCE_SPEED : f32 = 4.0;
+const HAMMER_SPEED_X : f32 = 2.0;
+const HAMMER_SPEED_Y : f32 = 8.0;
+const BULLET_SPEED : f32 = 3.0;
+
+// how far from the player projectiles fly before they are gone, and how long
+// a fireball puffs when it hits something.
+const RANGE : f32 = 640.0;
+const PUFF_TIME : f64 = 150.0;
+
+// how far away shooters see the player.
+const SIGHT_DISTANCE : f32 = 640.0;
+
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Projectile {
+    Fireball,
+    Hammer,
+    Bullet
+}
+
+impl Projectile {
+    pub fn from_name(name: &str) -> Option<Projectile> {
+        match name {
+            "fireball" => Some(Projectile::Fireball),
+            "hammer" => Some(Projectile::Hammer),
+            "bullet" => Some(Projectile::Bullet),
+            _ => None
+        }
+    }
+
+    // Fireballs kill enemies and hammers hurt the player, bullets are enemies
+    // themselves that can be stomped.
+    pub fn collider(&self) -> Collider {
+        match *self {
+            Projectile::Fireball => Collider::new(contact::PROJECTILE, contact::ENEMY),
+            Projectile::Hammer => Collider::new(contact::SHOT, contact::PLAYER),
+            Projectile::Bullet => Collider::new(contact::ENEMY, contact::PLAYER)
+        }
+    }
+
+    fn size(&self) -> u32 {
+        match *self {
+            Projectile::Fireball => 16,
+            Projectile::Hammer | Projectile::Bullet => 32
+        }
+    }
+}
+
+
+// A projectile of `kind` shot by `shooter`, just in front of it.
+pub fn shoot<'a>(kind: Projectile, shooter: &Transform, texture: &'a Texture) -> GameObject<'a> {
+    let size = kind.size() as f32;
+
+    let x = if shooter.facing < 0.0 { shooter.x - size } else { shooter.x + shooter.w as f32 };
+    let y = shooter.y + (shooter.h as f32 - size) / 2.0;
+
+    projectile(kind, x, y, shooter.facing, Box::new(ProjectileGraphicsComponent::new(texture, kind)))
+}
+
+// A projectile of `kind` at `x`, `y`, flying in `direction`.
+pub fn projectile<'a>(kind: Projectile, x: f32, y: f32, direction: f32, graphics: Box<dyn Renderable + 'a>) -> GameObject<'a> {
+    let velocity = match kind {
+        Projectile::Fireball => Velocity::new(FIREBALL_SPEED * direction, FIREBALL_BOUNCE_SPEED),
+        Projectile::Hammer => Velocity { solid: false, ..Velocity::new(HAMMER_SPEED_X * direction, -HAMMER_SPEED_Y) },
+        Projectile::Bullet => Velocity { gravity: 0.0, solid: false, ..Velocity::new(BULLET_SPEED * direction, 0.0) }
+    };
+
+    let mut object = GameObject::new(x, y)
+        .with_size(kind.size(), kind.size())
+        .with_velocity(velocity)
+        .with_collider(kind.collider())
+        .with_graphics(graphics)
+        .with_ai(Box::new(ProjectilePhysicsComponent::new(kind)));
+
+    object.transform.facing = direction;
+
+    object
+}
+
+// A cannon that shoots `projectile` every `interval` milliseconds.
+pub fn cannon<'a>(x: f32, y: f32, projectile: Projectile, interval: f64, graphics: Box<dyn Renderable + 'a>) -> GameObject<'a> {
+    GameObject::new(x, y)
+        .with_velocity(Velocity::still())
+        .with_graphics(graphics)
+        .with_ai(Box::new(ShooterPhysicsComponent::new(projectile, interval)))
+}
+
+
+// Keeps track of the projectiles that one shooter has out, so that it never
+// has more than `limit` of them at once.
+pub struct Launcher {
+    limit: usize,
+    shots: Vec<Entity>
+}
+
+impl Launcher {
+    pub fn new(limit: usize) -> Launcher {
+        Launcher {
+            limit: limit,
+            shots: Vec::new()
+        }
+    }
+
+    // Spawns `projectile` unless the limit is reached.
+    pub fn launch<'a>(&mut self, world: &mut World<'a>, projectile: GameObject<'a>) -> Option<Entity> {
+        self.shots.retain(|&shot| world.exists(shot));
+
+        if self.shots.len() >= self.limit {
+            return None;
+        }
+
+        let shot = world.spawn(projectile);
+
+        self.shots.push(shot);
+
+        Some(shot)
+    }
+}
+
+
+// Fireballs bounce along the ground and puff when they hit a wall or an
+// enemy, hammers fly in an arc through everything and bullets fly straight
+// until they are stomped.
+pub struct ProjectilePhysicsComponent {
+    kind: Projectile,
+    puff_time: f64
+}
+
+impl ProjectilePhysicsComponent {
+    pub fn new(kind: Projectile) -> ProjectilePhysicsComponent {
+        ProjectilePhysicsComponent {
+            kind: kind,
+            puff_time: 0.0
+        }
+    }
+
+    fn puff(&mut self, actor: &mut Actor) {
+        *actor.pose = Pose::Puff;
+        *actor.collider = None;
+        *actor.velocity = Velocity::still();
+
+        self.puff_time = PUFF_TIME;
+    }
+}
+
+impl Updatable for ProjectilePhysicsComponent {
+    fn update(&mut self, actor: &mut Actor, context: &Context) -> Vec<Event> {
+        if *actor.pose == Pose::Puff {
+            self.puff_time -= context.dt;
+
+            *actor.removed = self.puff_time <= 0.0;
+
+            return Vec::new();
+        }
+
+        if self.kind == Projectile::Fireball {
+            if actor.velocity.blocked.left || actor.velocity.blocked.right {
+                self.puff(actor);
+
+                return Vec::new();
+            }
+
+            if actor.velocity.blocked.bottom {
+                actor.velocity.dy = -FIREBALL_BOUNCE_SPEED;
+            }
+        }
+
+        if let Some(player) = context.player {
+            if (actor.transform.x - player.x() as f32).abs() > RANGE {
+                *actor.removed = true;
+            }
+        }
+
+        Vec::new()
+    }
+
+    fn touch(&mut self, actor: &mut Actor, touch: &Touch, _: &Context) -> Vec<Event> {
+        match self.kind {
+            Projectile::Fireball if touch.layer & contact::ENEMY != 0 => self.puff(actor),
+            Projectile::Bullet if touch.layer & contact::PLAYER != 0 && touch.side == Side::Bottom => {
+                *actor.pose = Pose::KnockedOut;
+                *actor.collider = None;
+                *actor.velocity = Velocity { solid: false, ..Velocity::new(0.0, 0.0) };
+
+                return vec![Event::Stomped];
+            },
+            _ => ()
+        }
+
+        Vec::new()
+    }
+}
+
+
+pub struct ProjectileGraphicsComponent<'a> {
+    kind: Projectile,
+    sprite_flying: AnimatedSprite<'a>,
+    sprite_puff: AnimatedSprite<'a>
+}
+
+impl<'a> ProjectileGraphicsComponent<'a> {
+    pub fn new(texture: &'a Texture, kind: Projectile) -> ProjectileGraphicsComponent<'a> {
+        let sprite_flying = match kind {
+            Projectile::Fireball => AnimatedSprite::new(texture, 0, 0, 4, 16.0),
+            Projectile::Hammer => AnimatedSprite::new(texture, 112, 0, 2, 8.0),
+            Projectile::Bullet => AnimatedSprite::new(texture, 144, 0, 1, 1.0)
+        };
+
+        ProjectileGraphicsComponent {
+            kind: kind,
+            sprite_flying: sprite_flying,
+            sprite_puff: AnimatedSprite::new(texture, 64, 0, 3, 20.0)
+        }
+    }
+}
+
+impl<'a> Renderable for ProjectileGraphicsComponent<'a> {
+    fn render(&mut self, look: &Look, elapsed: f64, renderer: &mut Renderer, destination: &Rect) {
+        if look.pose == Pose::Puff {
+            self.sprite_puff.render(elapsed, renderer, destination);
+        } else {
+            // bullets face left.
+            self.sprite_flying.flip_horizontal = (look.facing < 0.0) != (self.kind == Projectile::Bullet);
+            self.sprite_flying.flip_vertical = look.pose == Pose::KnockedOut;
+            self.sprite_flying.render(elapsed, renderer, destination);
+        }
+    }
+}
+
+
+// Shoots a `projectile` towards the player every `interval` milliseconds,
+// while the player is near and can be seen.
+pub struct ShooterPhysicsComponent {
+    projectile: Projectile,
+    interval: f64,
+    time: f64
+}
+
+impl ShooterPhysicsComponent {
+    pub fn new(projectile: Projectile, interval: f64) -> ShooterPhysicsComponent {
+        ShooterPhysicsComponent {
+            projectile: projectile,
+            interval: interval,
+            time: 0.0
+        }
+    }
+}
+
+impl Updatable for ShooterPhysicsComponent {
+    fn update(&mut self, actor: &mut Actor, context: &Context) -> Vec<Event> {
+        let player = match context.player {
+            Some(player) => player,
+            None => return Vec::new()
+        };
+
+        let from = enemy_components::center(&actor.transform.to_rect());
+        let to = enemy_components::center(&player);
+
+        self.time += context.dt;
+
+        if self.time < self.interval || (to.0 - from.0).abs() > SIGHT_DISTANCE || !context.map.has_line_of_sight(from, to) {
+            return Vec::new();
+        }
+
+        self.time = 0.0;
+
+        actor.transform.facing = if to.0 < from.0 { -1.0 } else { 1.0 };
+
+        vec![Event::Shot(self.projectile)]
+    }
+}
+
+
+pub struct CannonGraphicsComponent<'a> {
+    sprite: StaticSprite<'a>
+}
+
+impl<'a> CannonGraphicsComponent<'a> {
+    pub fn new(texture: &'a Texture) -> CannonGraphicsComponent<'a> {
+        CannonGraphicsComponent {
+            sprite: StaticSprite::new(texture, 160, 0)
+        }
+    }
+}
+
+impl<'a> Renderable for CannonGraphicsComponent<'a> {
+    fn render(&mut self, _: &Look, elapsed: f64, renderer: &mut Renderer, destination: &Rect) {
+        self.sprite.render(elapsed, renderer, destination);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sdl2::rect::Rect;
+
+    use component::{TICK, Input};
+    use map::Map;
+    use test_util::{self, Invisible};
+
+    use super::*;
+
+    fn map() -> Map {
+        // a floor with a wall at the right end of it.
+        test_util::map(&[
+            "..........",
+            "..........",
+            ".........#",
+            "##########"
+        ])
+    }
+
+    #[test]
+    fn fireball_bounces_and_puffs() {
+        let map = map();
+        let context = Context { map: &map, input: Input::default(), player: Some(Rect::new(0, 64, 32, 32)), dt: TICK };
+
+        let mut world = World::new(32);
+        let fireball = world.spawn(projectile(Projectile::Fireball, 32.0, 72.0, 1.0, Box::new(Invisible)));
+
+        // bounces off the floor, and stays above it.
+        let mut bounced = false;
+
+        while world.pose(fireball) != Some(Pose::Puff) {
+            world.update(&context);
+
+            bounced |= world.velocity(fireball).unwrap().dy < 0.0;
+
+            assert!(world.transform(fireball).unwrap().y <= 80.0);
+        }
+
+        assert!(bounced);
+        assert_eq!(world.transform(fireball).unwrap().x, 272.0);
+
+        for _ in 0..((PUFF_TIME / TICK) as usize) {
+            world.update(&context);
+        }
+
+        assert!(!world.exists(fireball));
+    }
+
+    #[test]
+    fn shooter_and_launcher() {
+        let map = map();
+        let context = Context { map: &map, input: Input::default(), player: Some(Rect::new(0, 64, 32, 32)), dt: TICK };
+
+        // shoots every so often, towards the player.
+        let mut world = World::new(32);
+        let shooter = world.spawn(cannon(160.0, 64.0, Projectile::Bullet, 100.0, Box::new(Invisible)));
+        let mut events = Vec::new();
+
+        for _ in 0..20 {
+            events.extend(world.update(&context));
+        }
+
+        assert_eq!(events, vec![(shooter, Event::Shot(Projectile::Bullet)), (shooter, Event::Shot(Projectile::Bullet))]);
+        assert_eq!(world.transform(shooter).unwrap().facing, -1.0);
+
+        // does not shoot through walls.
+        let hidden = Context { player: Some(Rect::new(320, 64, 32, 32)), ..context };
+        let behind = world.spawn(cannon(224.0, 64.0, Projectile::Bullet, 100.0, Box::new(Invisible)));
+
+        for _ in 0..20 {
+            assert!(!world.update(&hidden).contains(&(behind, Event::Shot(Projectile::Bullet))));
+        }
+
+        world.destroy(behind);
+
+        // has no more than two projectiles out at once.
+        let mut launcher = Launcher::new(2);
+
+        let bullet = || projectile(Projectile::Bullet, 0.0, 0.0, 1.0, Box::new(Invisible));
+
+        assert!(launcher.launch(&mut world, bullet()).is_some());
+
+        world.update(&context);
+
+        let second = launcher.launch(&mut world, bullet()).unwrap();
+
+        assert!(launcher.launch(&mut world, bullet()).is_none());
+
+        world.update(&context);
+        world.destroy(second);
+        world.update(&context);
+
+        assert!(launcher.launch(&mut world, bullet()).is_some());
+    }
+}
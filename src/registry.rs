@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use sdl2::rect::Rect;
+
+use collision::{Shape, Slope};
+use item_components::Item;
+
+
+const SOURCE_SIZE : u32 = 16;
+
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct TileType {
+    pub name: String,
+    pub source: Rect,
+    pub solid: bool,
+    pub one_way: bool,
+    pub slope: Option<Slope>,
+    pub hazard: bool,
+    pub climbable: bool,
+    pub liquid: bool,
+    pub friction: f32,
+    pub bounciness: f32,
+    pub breakable: bool,
+    pub coin: bool,
+    pub contents: Option<Item>,
+    pub used: Option<Rect>,
+    pub frames: Vec<Rect>,
+    pub frame_time: f64
+}
+
+impl TileType {
+    pub fn new(name: &str, source: Rect) -> TileType {
+        TileType {
+            name: name.to_string(),
+            source: source,
+            solid: false,
+            one_way: false,
+            slope: None,
+            hazard: false,
+            climbable: false,
+            liquid: false,
+            friction: 1.0,
+            bounciness: 0.0,
+            breakable: false,
+            coin: false,
+            contents: None,
+            used: None,
+            frames: Vec::new(),
+            frame_time: 0.0
+        }
+    }
+
+    pub fn shape(&self) -> Shape {
+        if self.solid {
+            Shape::Solid
+        } else if let Some(slope) = self.slope {
+            Shape::Slope(slope)
+        } else if self.one_way {
+            Shape::Platform
+        } else {
+            Shape::Empty
+        }
+    }
+
+    // The part of the tileset to draw at `time`, so that every tile of an
+    // animated type shows the same frame.
+    pub fn frame(&self, time: f64) -> Rect {
+        if self.frames.is_empty() || self.frame_time <= 0.0 {
+            return self.source;
+        }
+
+        self.frames[(time / self.frame_time) as usize % self.frames.len()]
+    }
+
+    // What a block turns into once its contents are taken, drawn from the
+    // `used` part of the tileset.
+    pub fn used_up(&self) -> TileType {
+        let mut used = self.clone();
+
+        used.source = self.used.unwrap_or(self.source);
+        used.frames = Vec::new();
+        used.breakable = false;
+        used.contents = None;
+        used.used = None;
+
+        used
+    }
+
+    // Sets one of the properties shared by the registry and Tiled tilesets,
+    // returns false if there is no property called `name`.
+    pub fn set_property(&mut self, name: &str, value: &str) -> Result<bool, String> {
+        match name {
+            "solid" => self.solid = parse_flag(value)?,
+            "one-way" => self.one_way = parse_flag(value)?,
+            "hazard" => self.hazard = parse_flag(value)?,
+            "climbable" => self.climbable = parse_flag(value)?,
+            "liquid" => self.liquid = parse_flag(value)?,
+            "friction" => self.friction = parse_value(value)?,
+            "bounciness" => self.bounciness = parse_value(value)?,
+            "breakable" => self.breakable = parse_flag(value)?,
+            "coin" => self.coin = parse_flag(value)?,
+            "contents" => match Item::from_name(value) {
+                Some(item) => self.contents = Some(item),
+                None => return Err(format!("unknown item `{}`", value))
+            },
+            "slope" => match Slope::from_name(value) {
+                Some(slope) => self.slope = Some(slope),
+                None => return Err(format!("unknown slope `{}`", value))
+            },
+            _ => return Ok(false)
+        }
+
+        Ok(true)
+    }
+}
+
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Syntax(usize, String),
+    Duplicate(usize, String),
+    OutOfRange(usize, u32, u32)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Syntax(line, ref message) => write!(f, "line {}: {}", line, message),
+            Error::Duplicate(line, ref name) => write!(f, "line {}: tile type '{}' is already defined", line, name),
+            Error::OutOfRange(line, x, y) => write!(f, "line {}: tile ({}, {}) is outside of the tileset", line, x, y)
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+
+pub struct Registry {
+    types: HashMap<String, Rc<TileType>>,
+    columns: u32,
+    rows: u32
+}
+
+impl Registry {
+    pub fn new(columns: u32, rows: u32) -> Registry {
+        Registry {
+            types: HashMap::new(),
+            columns: columns,
+            rows: rows
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let mut source = String::new();
+
+        File::open(path)?.read_to_string(&mut source)?;
+
+        self.parse(&source)
+    }
+
+    pub fn parse(&mut self, source: &str) -> Result<(), Error> {
+        for (number, line) in source.lines().enumerate().map(|(i, line)| (i + 1, line.trim())) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let words = line.split_whitespace().collect::<Vec<_>>();
+
+            if words.len() < 3 {
+                return Err(Error::Syntax(number, "expected `<name> <column> <row> [properties]`".to_string()));
+            }
+
+            let (x, y) = (parse_number(number, words[1])?, parse_number(number, words[2])?);
+            let mut tile = TileType::new(words[0], self.source(number, x, y)?);
+
+            let mut words = words[3..].iter();
+
+            while let Some(word) = words.next() {
+                let mut next = || words.next().copied().unwrap_or("");
+
+                let known = match *word {
+                    "frames" => {
+                        let count = parse_number(number, next())?;
+                        let fps: f64 = parse_number(number, next())?;
+
+                        tile.frames = (0..count).map(|i| self.source(number, x + i, y)).collect::<Result<_, _>>()?;
+                        tile.frame_time = 1000.0 / fps;
+
+                        Ok(true)
+                    },
+                    "used" => {
+                        let (x, y) = (parse_number(number, next())?, parse_number(number, next())?);
+
+                        tile.used = Some(self.source(number, x, y)?);
+
+                        Ok(true)
+                    },
+                    "solid" | "one-way" | "hazard" | "climbable" | "liquid" | "breakable" | "coin" => tile.set_property(word, "true"),
+                    name => tile.set_property(name, next())
+                }.map_err(|message| Error::Syntax(number, message))?;
+
+                if !known {
+                    return Err(Error::Syntax(number, format!("unknown property `{}`", word)));
+                }
+            }
+
+            if self.types.contains_key(&tile.name) {
+                return Err(Error::Duplicate(number, tile.name));
+            }
+
+            self.types.insert(tile.name.clone(), Rc::new(tile));
+        }
+
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Rc<TileType>> {
+        self.types.get(name)
+    }
+
+    fn source(&self, number: usize, x: u32, y: u32) -> Result<Rect, Error> {
+        if x >= self.columns || y >= self.rows {
+            return Err(Error::OutOfRange(number, x, y));
+        }
+
+        Ok(Rect::new((x * SOURCE_SIZE) as i32, (y * SOURCE_SIZE) as i32, SOURCE_SIZE, SOURCE_SIZE))
+    }
+}
+
+
+fn parse_flag(value: &str) -> Result<bool, String> {
+    match value {
+        "true" => Ok(true),
+        "false" => Ok(false),
+        value => Err(format!("expected `true` or `false`, found `{}`", value))
+    }
+}
+
+fn parse_value<T: FromStr>(value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("expected a number, found `{}`", value))
+}
+
+fn parse_number<T: FromStr>(number: usize, word: &str) -> Result<T, Error> {
+    parse_value(word).map_err(|message| Error::Syntax(number, message))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registry_parse() {
+        let mut registry = Registry::new(4, 2);
+
+        registry.parse("# comment\nsky 0 0\nice 1 0 solid friction 0.25\ncloud 2 0 one-way\nhill 3 0 slope up\nlava 0 1 hazard liquid frames 3 10\n").ok().unwrap();
+
+        assert_eq!(registry.get("sky").unwrap().shape(), Shape::Empty);
+        assert_eq!(registry.get("cloud").unwrap().shape(), Shape::Platform);
+        assert_eq!(registry.get("hill").unwrap().shape(), Shape::Slope(Slope::Up));
+
+        let ice = registry.get("ice").unwrap();
+
+        assert_eq!((ice.source, ice.shape(), ice.friction), (Rect::new(16, 0, 16, 16), Shape::Solid, 0.25));
+
+        let lava = registry.get("lava").unwrap();
+
+        assert_eq!((lava.hazard, lava.liquid, lava.frame_time), (true, true, 100.0));
+        assert_eq!(lava.frames, vec![Rect::new(0, 16, 16, 16), Rect::new(16, 16, 16, 16), Rect::new(32, 16, 16, 16)]);
+
+        assert_eq!(lava.frame(0.0), Rect::new(0, 16, 16, 16));
+        assert_eq!(lava.frame(250.0), Rect::new(32, 16, 16, 16));
+        assert_eq!(lava.frame(3120.0), Rect::new(16, 16, 16, 16));
+        assert_eq!(ice.frame(250.0), ice.source);
+
+        assert!(registry.get("water").is_none());
+
+        registry.parse("brick 2 1 solid breakable\nquestion 3 1 solid contents 1-up used 1 1\n").ok().unwrap();
+
+        let question = registry.get("question").unwrap();
+
+        assert_eq!((question.contents, question.used), (Some(Item::OneUp), Some(Rect::new(16, 16, 16, 16))));
+        assert_eq!(question.used_up().source, Rect::new(16, 16, 16, 16));
+        assert_eq!(question.used_up().contents, None);
+        assert!(registry.get("brick").unwrap().breakable);
+
+        registry.parse("coin 0 1 coin frames 3 4\n").ok().unwrap();
+
+        assert!(registry.get("coin").unwrap().coin);
+
+        match Registry::new(4, 2).parse("sky 0 0\nsky 1 0\n") {
+            Err(Error::Duplicate(2, _)) => (),
+            _ => panic!("expected duplicate")
+        }
+
+        match Registry::new(4, 2).parse("sky 4 0\n") {
+            Err(Error::OutOfRange(1, 4, 0)) => (),
+            _ => panic!("expected out of range")
+        }
+
+        match Registry::new(4, 2).parse("lava 2 1 frames 3 10\n") {
+            Err(Error::OutOfRange(1, 4, 1)) => (),
+            _ => panic!("expected out of range")
+        }
+
+        match Registry::new(4, 2).parse("box 0 0 contents socks\n") {
+            Err(Error::Syntax(1, _)) => (),
+            _ => panic!("expected unknown item")
+        }
+
+        match Registry::new(4, 2).parse("sky 0 0 fluffy\n") {
+            Err(Error::Syntax(1, _)) => (),
+            _ => panic!("expected unknown property")
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::fmt;
+
+use component::Event;
+use item_components::Item;
+use world::Entity;
+
+
+// points for each stomp in a row without landing, after which every stomp
+// is worth a life.
+const STOMP_POINTS : [u32; 10] = [100, 200, 400, 500, 800, 1000, 2000, 4000, 5000, 8000];
+
+const KILL_POINTS : u32 = 100;
+const KICK_POINTS : u32 = 400;
+const COIN_POINTS : u32 = 200;
+const ITEM_POINTS : u32 = 1000;
+const BRICK_POINTS : u32 = 50;
+
+const COINS_PER_LIFE : u32 = 100;
+
+// points for each unit of time left at the end of a level.
+const TIME_POINTS : u32 = 50;
+
+
+// Everything about a game that outlives a level.
+pub struct GameSession {
+    pub world: u32,
+    pub level: u32,
+    pub score: u32,
+    pub coins: u32,
+    pub lives: u32,
+    combo: usize
+}
+
+impl GameSession {
+    pub fn new(world: u32, level: u32, lives: u32) -> GameSession {
+        GameSession {
+            world: world,
+            level: level,
+            score: 0,
+            coins: 0,
+            lives: lives,
+            combo: 0
+        }
+    }
+
+    // Awards what the events of a tick are worth, this is the only place
+    // that points are given. Stomps in a row are worth more and more until
+    // the player lands.
+    pub fn award(&mut self, events: &[(Entity, Event)], player: Entity) {
+        for &(entity, event) in events {
+            match event {
+                Event::Landed if entity == player => self.combo = 0,
+                Event::Stomped => {
+                    match STOMP_POINTS.get(self.combo) {
+                        Some(&points) => self.score += points,
+                        None => self.lives += 1
+                    }
+
+                    self.combo += 1;
+                },
+                Event::Killed if entity != player => self.score += KILL_POINTS,
+                Event::Kicked => self.score += KICK_POINTS,
+                Event::Shattered => self.score += BRICK_POINTS,
+                Event::Collected(Item::Coin) => {
+                    self.score += COIN_POINTS;
+                    self.coins += 1;
+
+                    if self.coins >= COINS_PER_LIFE {
+                        self.coins -= COINS_PER_LIFE;
+                        self.lives += 1;
+                    }
+                },
+                Event::Collected(Item::OneUp) => self.lives += 1,
+                Event::Collected(_) => self.score += ITEM_POINTS,
+                _ => ()
+            }
+        }
+    }
+
+    pub fn award_time(&mut self, time: u32) {
+        self.score += time * TIME_POINTS;
+    }
+
+    // Takes a life from the player, and returns whether the game is over.
+    pub fn lose_life(&mut self) -> bool {
+        self.lives = self.lives.saturating_sub(1);
+        self.combo = 0;
+
+        self.lives == 0
+    }
+}
+
+impl fmt::Display for GameSession {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "WORLD {}-{}  SCORE {:06}  COINS {:02}  LIVES {}", self.world, self.level, self.score, self.coins, self.lives)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use game_object::GameObject;
+    use world::World;
+
+    use super::*;
+
+    #[test]
+    fn session_award() {
+        let mut world = World::new(32);
+
+        let player = world.spawn(GameObject::new(0.0, 0.0));
+        let enemy = world.spawn(GameObject::new(0.0, 0.0));
+
+        let mut session = GameSession::new(1, 1, 3);
+
+        // stomps in a row are worth more, until the player lands.
+        session.award(&[(enemy, Event::Stomped), (enemy, Event::Stomped), (enemy, Event::Stomped)], player);
+
+        assert_eq!(session.score, 700);
+
+        session.award(&[(enemy, Event::Landed), (enemy, Event::Stomped)], player);
+
+        assert_eq!(session.score, 1200);
+
+        session.award(&[(player, Event::Landed), (enemy, Event::Stomped), (player, Event::Killed)], player);
+
+        assert_eq!(session.score, 1300);
+
+        for _ in 0..10 {
+            session.award(&[(enemy, Event::Stomped)], player);
+        }
+
+        assert_eq!(session.lives, 4);
+
+        // every hundredth coin is worth a life.
+        for _ in 0..150 {
+            session.award(&[(enemy, Event::Collected(Item::Coin))], player);
+        }
+
+        assert_eq!((session.coins, session.lives), (50, 5));
+        assert_eq!(format!("{}", session), "WORLD 1-1  SCORE 053200  COINS 50  LIVES 5");
+
+        // the game is over when the last life is lost.
+        for _ in 0..4 {
+            assert!(!session.lose_life());
+        }
+
+        assert!(session.lose_life());
+
+        session.award_time(10);
+
+        assert_eq!(session.score, 53700);
+    }
+}
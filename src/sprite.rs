@@ -1,16 +1,16 @@
-use std::cell::RefCell;
-
 use sdl2::render::{Texture, Renderer};
 use sdl2::rect::Rect;
 
 pub trait Sprite {
-    fn render(&self, f64, &mut Renderer, &Rect);
+    fn render(&mut self, f64, &mut Renderer, &Rect);
 }
 
 pub struct StaticSprite<'a> {
     texture: &'a Texture,
     x: i32,
     y: i32,
+    width: u32,
+    height: u32,
     pub flip_horizontal: bool,
     pub flip_vertical: bool
 }
@@ -21,15 +21,24 @@ impl<'a> StaticSprite<'a> {
             texture: texture,
             x: x,
             y: y,
+            width: 16,
+            height: 16,
             flip_horizontal: false,
             flip_vertical: false
         }
     }
+
+    // Draws a larger part of the texture than a single 16x16 cell.
+    pub fn with_size(mut self, width: u32, height: u32) -> StaticSprite<'a> {
+        self.width = width;
+        self.height = height;
+        self
+    }
 }
 
 impl<'a> Sprite for StaticSprite<'a> {
-    fn render(&self, _: f64, drawer: &mut Renderer, destination: &Rect) {
-        let _ = drawer.copy_ex(self.texture, Some(Rect::new(self.x, self.y, 16, 16)), Some(*destination), 0.0, None, self.flip_horizontal, self.flip_vertical);
+    fn render(&mut self, _: f64, drawer: &mut Renderer, destination: &Rect) {
+        let _ = drawer.copy_ex(self.texture, Some(Rect::new(self.x, self.y, self.width, self.height)), Some(*destination), 0.0, None, self.flip_horizontal, self.flip_vertical);
     }
 }
 
@@ -37,12 +46,14 @@ pub struct AnimatedSprite<'a> {
     texture: &'a Texture,
     x: i32,
     y: i32,
+    width: u32,
+    height: u32,
     pub flip_horizontal: bool,
     pub flip_vertical: bool,
     frames: u32,
     frame_time: f64,
-    frame: RefCell<u32>,
-    time: RefCell<f64>
+    frame: u32,
+    time: f64
 }
 
 impl<'a> AnimatedSprite<'a> {
@@ -51,28 +62,33 @@ impl<'a> AnimatedSprite<'a> {
             texture: texture,
             x: x,
             y: y,
+            width: 16,
+            height: 16,
             flip_horizontal: false,
             flip_vertical: false,
-            frame: RefCell::new(0),
+            frame: 0,
             frames: frames,
-            time: RefCell::new(0.0),
+            time: 0.0,
             frame_time: 1000.0 / fps as f64
         }
     }
+
+    pub fn with_size(mut self, width: u32, height: u32) -> AnimatedSprite<'a> {
+        self.width = width;
+        self.height = height;
+        self
+    }
 }
 
 impl<'a> Sprite for AnimatedSprite<'a> {
-    fn render(&self, elapsed: f64, drawer: &mut Renderer, destination: &Rect) {
-        let mut time = self.time.borrow_mut();
-        let mut frame = self.frame.borrow_mut();
-
-        *time += elapsed;
-        *frame += (*time / self.frame_time) as u32;
-        *time %= self.frame_time;
-        *frame %= self.frames;
+    fn render(&mut self, elapsed: f64, drawer: &mut Renderer, destination: &Rect) {
+        self.time += elapsed;
+        self.frame += (self.time / self.frame_time) as u32;
+        self.time %= self.frame_time;
+        self.frame %= self.frames;
 
-        let x = self.x + (*frame * 16) as i32;
+        let x = self.x + (self.frame * self.width) as i32;
 
-        let _ = drawer.copy_ex(self.texture, Some(Rect::new(x, self.y, 16, 16)), Some(*destination), 0.0, None, self.flip_horizontal, self.flip_vertical);
+        let _ = drawer.copy_ex(self.texture, Some(Rect::new(x, self.y, self.width, self.height)), Some(*destination), 0.0, None, self.flip_horizontal, self.flip_vertical);
     }
 }
@@ -0,0 +1,47 @@
+use std::rc::Rc;
+
+use sdl2::rect::Rect;
+use sdl2::render::Renderer;
+
+use component::{Renderable, Look};
+use map::{Map, Pass, Parallax};
+use level::Tile;
+use registry::TileType;
+
+
+pub struct Invisible;
+
+impl Renderable for Invisible {
+    fn render(&mut self, _: &Look, _: f64, _: &mut Renderer, _: &Rect) {
+    }
+}
+
+
+pub fn solid(name: &str) -> TileType {
+    let mut kind = TileType::new(name, Rect::new(0, 0, 16, 16));
+
+    kind.solid = true;
+
+    kind
+}
+
+// A map of 32x32 tiles drawn as `rows` of text, where `#` is a solid tile and
+// anything else is empty.
+pub fn map(rows: &[&str]) -> Map {
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0) as u32;
+
+    let mut map = Map::new(width, rows.len() as u32, 32, 32);
+
+    let floor = Tile::Defined(Rc::new(solid("floor")));
+    let layer = map.add_layer(Pass::Collision, width, Parallax::none());
+
+    for (y, row) in rows.iter().enumerate() {
+        for (x, c) in row.chars().enumerate() {
+            if c == '#' {
+                layer.set_tile(x as i32, y as i32, floor.clone());
+            }
+        }
+    }
+
+    map
+}
@@ -1,6 +1,27 @@
+use std::collections::HashMap;
+
 use sdl2::rect::Rect;
 
 
+const CHUNK_SIZE : i32 = 32;
+
+
+#[derive(Debug, PartialEq)]
+pub struct RayHit {
+    pub tile: (i32, i32),
+    pub point: (f32, f32),
+    pub normal: (i32, i32),
+    pub distance: f32
+}
+
+
+#[derive(Debug, PartialEq)]
+pub struct OutOfBounds {
+    pub x: i32,
+    pub y: i32
+}
+
+
 pub struct Layer<T> {
     tiles: Vec<Option<T>>,
     default: T,
@@ -23,19 +44,42 @@ impl<T> Layer<T> where T: Clone {
         }
     }
 
-    pub fn get_tile(&self, x: i32, y: i32) -> &T {
-        let offset = (x + y * self.width as i32) as usize;
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= 0 && y >= 0 && x < self.width as i32 && y < self.height as i32
+    }
+
+    pub fn try_get_tile(&self, x: i32, y: i32) -> Option<&T> {
+        if !self.contains(x, y) {
+            return None;
+        }
 
-        match *self.tiles.get(offset).unwrap() {
-            Some(ref tile) => tile,
-            None           => &self.default
+        match self.tiles[(x + y * self.width as i32) as usize] {
+            Some(ref tile) => Some(tile),
+            None           => Some(&self.default)
         }
     }
 
-    pub fn set_tile(&mut self, x: i32, y: i32, tile: T) {
-        let offset = (x + y * self.width as i32) as usize;
+    pub fn try_set_tile(&mut self, x: i32, y: i32, tile: T) -> Result<(), OutOfBounds> {
+        if !self.contains(x, y) {
+            return Err(OutOfBounds { x: x, y: y });
+        }
+
+        self.tiles[(x + y * self.width as i32) as usize] = Some(tile);
 
-        self.tiles[offset] = Some(tile);
+        Ok(())
+    }
+
+    pub fn get_tile(&self, x: i32, y: i32) -> &T {
+        match self.try_get_tile(x, y) {
+            Some(tile) => tile,
+            None => panic!("tile ({}, {}) is outside of the {}x{} layer", x, y, self.width, self.height)
+        }
+    }
+
+    pub fn set_tile(&mut self, x: i32, y: i32, tile: T) {
+        if let Err(OutOfBounds { x, y }) = self.try_set_tile(x, y, tile) {
+            panic!("tile ({}, {}) is outside of the {}x{} layer", x, y, self.width, self.height);
+        }
     }
 
     pub fn find_intersecting(&self, rect: &Rect) -> Option<Rect> {
@@ -46,11 +90,12 @@ impl<T> Layer<T> where T: Clone {
             return None;
         }
 
-        let x1 = rect.x() / self.tile_width as i32;
-        let y1 = rect.y() / self.tile_height as i32;
+        // rects that are partly outside only intersect the tiles inside.
+        let x1 = (rect.x() / self.tile_width as i32).max(0);
+        let y1 = (rect.y() / self.tile_height as i32).max(0);
 
-        let x2 = (rect.x() + rect.width() as i32 - 1) / self.tile_width as i32;
-        let y2 = (rect.y() + rect.height() as i32 - 1) / self.tile_height as i32;
+        let x2 = ((rect.x() + rect.width() as i32 - 1) / self.tile_width as i32).min(self.width as i32 - 1);
+        let y2 = ((rect.y() + rect.height() as i32 - 1) / self.tile_height as i32).min(self.height as i32 - 1);
 
         Some(Rect::new(x1, y1, (x2 - x1 + 1) as u32, (y2 - y1 + 1) as u32))
     }
@@ -67,14 +112,182 @@ impl<T> Layer<T> where T: Clone {
         }
     }
 
-    pub fn to_rect(&self) -> Rect {
-        Rect::new(0, 0, self.width * self.tile_width, self.height * self.tile_height)
+    pub fn tile_width(&self) -> u32 {
+        self.tile_width
     }
+
+    pub fn tile_height(&self) -> u32 {
+        self.tile_height
+    }
+}
+
+
+// A layer without fixed bounds, stored as CHUNK_SIZE x CHUNK_SIZE chunks that
+// are only allocated once a tile is set in them. Coordinates can be negative,
+// and the layer grows to cover every tile that has been set.
+pub struct ChunkedLayer<T> {
+    chunks: HashMap<(i32, i32), Layer<T>>,
+    default: T,
+    bounds: Option<(i32, i32, i32, i32)>,
+    tile_width: u32,
+    tile_height: u32
+}
+
+impl<T> ChunkedLayer<T> where T: Clone {
+    pub fn new(tile_width: u32, tile_height: u32, tile: T) -> ChunkedLayer<T> {
+        ChunkedLayer {
+            chunks: HashMap::new(),
+            default: tile,
+            bounds: None,
+            tile_width: tile_width,
+            tile_height: tile_height
+        }
+    }
+
+    pub fn get_tile(&self, x: i32, y: i32) -> &T {
+        let (chunk, (local_x, local_y)) = chunk_position(x, y);
+
+        match self.chunks.get(&chunk) {
+            Some(tiles) => tiles.get_tile(local_x, local_y),
+            None => &self.default
+        }
+    }
+
+    pub fn set_tile(&mut self, x: i32, y: i32, tile: T) {
+        let (chunk, (local_x, local_y)) = chunk_position(x, y);
+
+        let (default, tile_width, tile_height) = (&self.default, self.tile_width, self.tile_height);
+
+        self.chunks.entry(chunk)
+            .or_insert_with(|| Layer::new(CHUNK_SIZE as u32, CHUNK_SIZE as u32, tile_width, tile_height, default.clone()))
+            .set_tile(local_x, local_y, tile);
+
+        self.bounds = Some(match self.bounds {
+            Some((x1, y1, x2, y2)) => (x1.min(x), y1.min(y), x2.max(x), y2.max(y)),
+            None => (x, y, x, y)
+        });
+    }
+
+    pub fn find_intersecting(&self, rect: &Rect) -> Option<Rect> {
+        let (bx1, by1, bx2, by2) = self.bounds?;
+
+        let x1 = rect.x().div_euclid(self.tile_width as i32).max(bx1);
+        let y1 = rect.y().div_euclid(self.tile_height as i32).max(by1);
+
+        let x2 = (rect.x() + rect.width() as i32 - 1).div_euclid(self.tile_width as i32).min(bx2);
+        let y2 = (rect.y() + rect.height() as i32 - 1).div_euclid(self.tile_height as i32).min(by2);
+
+        if x1 > x2 || y1 > y2 {
+            return None;
+        }
+
+        Some(Rect::new(x1, y1, (x2 - x1 + 1) as u32, (y2 - y1 + 1) as u32))
+    }
+
+    // Tiles in chunks that nothing was ever set in are skipped.
+    pub fn for_each_intersecting<F: FnMut(&T, &Rect)>(&self, rect: &Rect, mut f: F) {
+        let intersect = match self.find_intersecting(rect) {
+            Some(intersect) => intersect,
+            None => return
+        };
+
+        let (first, _) = chunk_position(intersect.x(), intersect.y());
+        let (last, _) = chunk_position(intersect.right() - 1, intersect.bottom() - 1);
+
+        for chunk_y in first.1..(last.1 + 1) {
+            for chunk_x in first.0..(last.0 + 1) {
+                if let Some(chunk) = self.chunks.get(&(chunk_x, chunk_y)) {
+                    let origin_x = chunk_x * CHUNK_SIZE * self.tile_width as i32;
+                    let origin_y = chunk_y * CHUNK_SIZE * self.tile_height as i32;
+
+                    let local = Rect::new(rect.x() - origin_x, rect.y() - origin_y, rect.width(), rect.height());
+
+                    chunk.for_each_intersecting(&local, |tile, position| {
+                        f(tile, &Rect::new(position.x() + origin_x, position.y() + origin_y, position.width(), position.height()));
+                    });
+                }
+            }
+        }
+    }
+
+    // The area covered by the tiles that have been set so far.
+    pub fn to_rect(&self) -> Option<Rect> {
+        self.bounds.map(|(x1, y1, x2, y2)| {
+            Rect::new(x1 * self.tile_width as i32, y1 * self.tile_height as i32, (x2 - x1 + 1) as u32 * self.tile_width, (y2 - y1 + 1) as u32 * self.tile_height)
+        })
+    }
+}
+
+// Walks the tiles along the segment from `from` to `to` in order, one grid line
+// at a time, and returns the first one that `is_solid`. A ray that starts in a
+// solid tile hits it straight away, with no normal.
+pub fn raycast<F: FnMut(i32, i32) -> bool>(tile_width: u32, tile_height: u32, from: (f32, f32), to: (f32, f32), mut is_solid: F) -> Option<RayHit> {
+    let (tile_width, tile_height) = (tile_width as f32, tile_height as f32);
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = (dx * dx + dy * dy).sqrt();
+
+    let mut x = (from.0 / tile_width).floor() as i32;
+    let mut y = (from.1 / tile_height).floor() as i32;
+
+    if is_solid(x, y) {
+        return Some(RayHit { tile: (x, y), point: from, normal: (0, 0), distance: 0.0 });
+    }
+
+    if length == 0.0 {
+        return None;
+    }
+
+    let (direction_x, direction_y) = (dx / length, dy / length);
+    let (step_x, step_y) = (dx.signum() as i32, dy.signum() as i32);
+
+    // distance along the ray to the next vertical and horizontal grid line.
+    let mut next_x = match step_x {
+        1 => ((x + 1) as f32 * tile_width - from.0) / direction_x,
+        -1 => (x as f32 * tile_width - from.0) / direction_x,
+        _ => f32::INFINITY
+    };
+    let mut next_y = match step_y {
+        1 => ((y + 1) as f32 * tile_height - from.1) / direction_y,
+        -1 => (y as f32 * tile_height - from.1) / direction_y,
+        _ => f32::INFINITY
+    };
+
+    let delta_x = tile_width / direction_x.abs();
+    let delta_y = tile_height / direction_y.abs();
+
+    loop {
+        let (distance, normal) = if next_x < next_y {
+            x += step_x;
+            next_x += delta_x;
+
+            (next_x - delta_x, (-step_x, 0))
+        } else {
+            y += step_y;
+            next_y += delta_y;
+
+            (next_y - delta_y, (0, -step_y))
+        };
+
+        if distance > length {
+            return None;
+        }
+
+        if is_solid(x, y) {
+            let point = (from.0 + direction_x * distance, from.1 + direction_y * distance);
+
+            return Some(RayHit { tile: (x, y), point: point, normal: normal, distance: distance });
+        }
+    }
+}
+
+// The chunk that tile (x, y) is in, and where in the chunk it is.
+fn chunk_position(x: i32, y: i32) -> ((i32, i32), (i32, i32)) {
+    ((x.div_euclid(CHUNK_SIZE), y.div_euclid(CHUNK_SIZE)), (x.rem_euclid(CHUNK_SIZE), y.rem_euclid(CHUNK_SIZE)))
 }
 
 #[cfg(test)]
 mod tests {
-    use sdl2::rect::Rect;
+    use sdl2::rect::{Point, Rect};
 
     use super::*;
 
@@ -132,5 +345,115 @@ mod tests {
 
         // exactly one tile.
         assert_eq!(layer.find_intersecting(&Rect::new(0, 0, 3, 3)), Some(Rect::new(0, 0, 1, 1)));
+
+        // partly out of bounds.
+        assert_eq!(layer.find_intersecting(&Rect::new(-2, -2, 4, 4)), Some(Rect::new(0, 0, 1, 1)));
+        assert_eq!(layer.find_intersecting(&Rect::new(7, 7, 4, 4)), Some(Rect::new(2, 2, 1, 1)));
+        assert_eq!(layer.find_intersecting(&Rect::new(-5, 4, 20, 1)), Some(Rect::new(0, 1, 3, 1)));
+    }
+
+    #[test]
+    fn layer_try_get_tile() {
+        let mut layer = Layer::new(3, 2, 3, 3, 0);
+
+        assert_eq!(layer.try_set_tile(2, 1, 1), Ok(()));
+        assert_eq!(layer.try_set_tile(3, 0, 2), Err(OutOfBounds { x: 3, y: 0 }));
+        assert_eq!(layer.try_set_tile(0, -1, 2), Err(OutOfBounds { x: 0, y: -1 }));
+
+        assert_eq!(layer.try_get_tile(0, 0), Some(&0));
+        assert_eq!(layer.try_get_tile(2, 1), Some(&1));
+
+        // would wrap around to the previous row.
+        assert_eq!(layer.try_get_tile(-1, 1), None);
+        assert_eq!(layer.try_get_tile(3, 0), None);
+        assert_eq!(layer.try_get_tile(0, 2), None);
+    }
+
+    #[test]
+    fn chunked_layer_get_set() {
+        let mut layer = ChunkedLayer::new(3, 3, 0);
+
+        assert_eq!(layer.to_rect(), None);
+        assert_eq!(*layer.get_tile(-100, 100), 0);
+
+        layer.set_tile(0, 0, 1);
+        layer.set_tile(-1, -1, 2);
+        layer.set_tile(31, 31, 3);
+        layer.set_tile(1000, 2, 4);
+
+        assert_eq!(layer.chunks.len(), 3);
+
+        assert_eq!(*layer.get_tile(0, 0), 1);
+        assert_eq!(*layer.get_tile(-1, -1), 2);
+        assert_eq!(*layer.get_tile(31, 31), 3);
+        assert_eq!(*layer.get_tile(1000, 2), 4);
+        assert_eq!(*layer.get_tile(-32, -1), 0);
+        assert_eq!(*layer.get_tile(999, 2), 0);
+
+        assert_eq!(layer.to_rect(), Some(Rect::new(-3, -3, 1002 * 3, 33 * 3)));
+    }
+
+    #[test]
+    fn chunked_layer_find_intersecting() {
+        let mut layer = ChunkedLayer::new(3, 3, ());
+
+        assert_eq!(layer.find_intersecting(&Rect::new(1, 1, 1, 1)), None);
+
+        layer.set_tile(-2, -2, ());
+        layer.set_tile(2, 2, ());
+
+        // outside of the tiles set so far.
+        assert_eq!(layer.find_intersecting(&Rect::new(-7, 1, 1, 1)), None);
+        assert_eq!(layer.find_intersecting(&Rect::new(9, 9, 1, 1)), None);
+
+        // negative coordinates.
+        assert_eq!(layer.find_intersecting(&Rect::new(-1, -1, 1, 1)), Some(Rect::new(-1, -1, 1, 1)));
+        assert_eq!(layer.find_intersecting(&Rect::new(-4, -2, 2, 4)), Some(Rect::new(-2, -1, 2, 2)));
+
+        // clipped to the tiles set so far.
+        assert_eq!(layer.find_intersecting(&Rect::new(-100, 4, 200, 1)), Some(Rect::new(-2, 1, 5, 1)));
+
+        let mut positions = Vec::new();
+
+        layer.for_each_intersecting(&Rect::new(-6, -6, 15, 15), |_, position| positions.push(position.top_left()));
+
+        // only the two chunks with tiles in them.
+        assert_eq!(positions.len(), 4 + 9);
+        assert_eq!(positions[0], Point::new(-6, -6));
+        assert_eq!(positions[4], Point::new(0, 0));
+        assert_eq!(positions[12], Point::new(6, 6));
+    }
+
+    #[test]
+    fn layer_raycast() {
+        // . . . .
+        // . . # .
+        // . . . .
+        // . . . .
+        let mut layer = Layer::new(4, 4, 10, 10, false);
+
+        layer.set_tile(2, 1, true);
+
+        let cast = |from, to| raycast(10, 10, from, to, |x, y| layer.try_get_tile(x, y) == Some(&true));
+
+        // hits the left side.
+        assert_eq!(cast((5.0, 15.0), (35.0, 15.0)), Some(RayHit { tile: (2, 1), point: (20.0, 15.0), normal: (-1, 0), distance: 15.0 }));
+
+        // hits the bottom.
+        assert_eq!(cast((25.0, 35.0), (25.0, 5.0)), Some(RayHit { tile: (2, 1), point: (25.0, 20.0), normal: (0, 1), distance: 15.0 }));
+
+        // hits the right side going down and left.
+        let hit = cast((38.0, 12.0), (18.0, 17.0)).unwrap();
+
+        assert_eq!((hit.tile, hit.normal), ((2, 1), (1, 0)));
+        assert!((hit.point.0 - 30.0).abs() < 0.001 && (hit.point.1 - 14.0).abs() < 0.001);
+
+        // starts inside.
+        assert_eq!(cast((25.0, 15.0), (0.0, 0.0)).map(|hit| hit.distance), Some(0.0));
+
+        // stops short, goes past and leaves the layer.
+        assert_eq!(cast((5.0, 15.0), (19.0, 15.0)), None);
+        assert_eq!(cast((5.0, 25.0), (35.0, 5.0)).map(|hit| hit.tile), Some((2, 1)));
+        assert_eq!(cast((5.0, 35.0), (95.0, 35.0)), None);
     }
 }
@@ -0,0 +1,832 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::rc::Rc;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use flate2::read::{GzDecoder, ZlibDecoder};
+use serde_json::Value;
+use sdl2::rect::Rect;
+use xml::reader::{EventReader, XmlEvent};
+
+use tile::ChunkedLayer;
+use level::Tile;
+use map::{Map, Object, Pass, Parallax, Image, Edges};
+use registry::TileType;
+
+
+const FLIPPED_MASK : u32 = 0xe0000000;
+
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(String),
+    Invalid(String),
+    Unsupported(String)
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => write!(f, "{}", err),
+            Error::Parse(ref message) => write!(f, "{}", message),
+            Error::Invalid(ref message) => write!(f, "invalid map: {}", message),
+            Error::Unsupported(ref message) => write!(f, "unsupported map: {}", message)
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+
+struct Tileset {
+    first_gid: u32,
+    image: String,
+    columns: u32,
+    tile_width: u32,
+    tile_height: u32,
+    margin: u32,
+    spacing: u32,
+    properties: HashMap<u32, HashMap<String, String>>,
+    animations: HashMap<u32, Vec<(u32, u32)>>
+}
+
+impl Tileset {
+    fn source(&self, id: u32) -> Rect {
+        let x = self.margin + (id % self.columns) * (self.tile_width + self.spacing);
+        let y = self.margin + (id / self.columns) * (self.tile_height + self.spacing);
+
+        Rect::new(x as i32, y as i32, self.tile_width, self.tile_height)
+    }
+}
+
+struct Context<'t> {
+    width: u32,
+    height: u32,
+    scale: (f32, f32),
+    tilesets: &'t [Tileset],
+    directory: &'t Path
+}
+
+struct Element {
+    name: String,
+    attributes: HashMap<String, String>,
+    children: Vec<Element>,
+    text: String
+}
+
+impl Element {
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes.get(name).map(|value| value.as_str())
+    }
+
+    fn number(&self, name: &str) -> Result<u32, Error> {
+        match self.attribute(name) {
+            Some(value) => value.parse().map_err(|_| Error::Invalid(format!("`{}` on <{}> is not a number", name, self.name))),
+            None => Err(Error::Invalid(format!("<{}> is missing `{}`", self.name, name)))
+        }
+    }
+
+    fn float(&self, name: &str, default: f32) -> Result<f32, Error> {
+        match self.attribute(name) {
+            Some(value) => value.parse().map_err(|_| Error::Invalid(format!("`{}` on <{}> is not a number", name, self.name))),
+            None => Ok(default)
+        }
+    }
+
+    fn elements<'e>(&'e self, name: &'e str) -> impl Iterator<Item=&'e Element> + 'e {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    fn properties(&self) -> HashMap<String, String> {
+        let mut properties = HashMap::new();
+
+        for group in self.elements("properties") {
+            for property in group.elements("property") {
+                if let Some(name) = property.attribute("name") {
+                    let value = property.attribute("value").map(|value| value.to_string()).unwrap_or_else(|| property.text.clone());
+
+                    properties.insert(name.to_string(), value);
+                }
+            }
+        }
+
+        properties
+    }
+}
+
+
+pub struct Importer {
+    image: String,
+    tile_width: u32,
+    tile_height: u32
+}
+
+impl Importer {
+    pub fn new(image: &str, tile_width: u32, tile_height: u32) -> Importer {
+        Importer {
+            image: image.to_string(),
+            tile_width: tile_width,
+            tile_height: tile_height
+        }
+    }
+
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> Result<Map, Error> {
+        let path = path.as_ref();
+        let source = read_file(path)?;
+        let directory = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("tmx") => self.parse_tmx(&source, &directory),
+            Some("tmj") | Some("json") => self.parse_tmj(&source, &directory),
+            _ => Err(Error::Unsupported(format!("unknown file type {}", path.display())))
+        }
+    }
+
+    pub fn parse_tmx(&self, source: &str, directory: &Path) -> Result<Map, Error> {
+        let root = parse_xml(source)?;
+
+        if root.name != "map" {
+            return Err(Error::Invalid(format!("expected <map>, found <{}>", root.name)));
+        }
+
+        check_orientation(root.attribute("orientation"), root.attribute("infinite") == Some("1"))?;
+
+        let width = root.number("width")?;
+        let height = root.number("height")?;
+        let scale_x = self.tile_width as f32 / root.number("tilewidth")? as f32;
+        let scale_y = self.tile_height as f32 / root.number("tileheight")? as f32;
+
+        let mut tilesets = Vec::new();
+
+        for element in root.elements("tileset") {
+            let first_gid = element.number("firstgid")?;
+
+            let tileset = match element.attribute("source") {
+                Some(source) => {
+                    let path = directory.join(source);
+
+                    self.read_tileset(first_gid, &path)?
+                },
+                None => parse_tsx_tileset(first_gid, element)?
+            };
+
+            tilesets.push(tileset);
+        }
+
+        let mut map = Map::new(width, height, self.tile_width, self.tile_height);
+
+        map.edges = map_edges(root.properties().get("edges"))?;
+
+        let context = Context {
+            width: width,
+            height: height,
+            scale: (scale_x, scale_y),
+            tilesets: &tilesets,
+            directory: directory
+        };
+
+        self.parse_tmx_layers(&root, &context, &mut map)?;
+
+        Ok(map)
+    }
+
+    pub fn parse_tmj(&self, source: &str, directory: &Path) -> Result<Map, Error> {
+        let root = parse_json(source)?;
+
+        check_orientation(root["orientation"].as_str(), root["infinite"].as_bool() == Some(true))?;
+
+        let width = json_number(&root, "width")?;
+        let height = json_number(&root, "height")?;
+        let scale_x = self.tile_width as f32 / json_number(&root, "tilewidth")? as f32;
+        let scale_y = self.tile_height as f32 / json_number(&root, "tileheight")? as f32;
+
+        let mut tilesets = Vec::new();
+
+        for value in json_array(&root, "tilesets")? {
+            let first_gid = json_number(value, "firstgid")?;
+
+            let tileset = match value["source"].as_str() {
+                Some(source) => {
+                    let path = directory.join(source);
+
+                    self.read_tileset(first_gid, &path)?
+                },
+                None => parse_tsj_tileset(first_gid, value)?
+            };
+
+            tilesets.push(tileset);
+        }
+
+        let mut map = Map::new(width, height, self.tile_width, self.tile_height);
+
+        map.edges = map_edges(json_properties(&root).get("edges"))?;
+
+        let context = Context {
+            width: width,
+            height: height,
+            scale: (scale_x, scale_y),
+            tilesets: &tilesets,
+            directory: directory
+        };
+
+        self.parse_tmj_layers(&root, &context, &mut map)?;
+
+        Ok(map)
+    }
+
+    fn read_tileset(&self, first_gid: u32, path: &Path) -> Result<Tileset, Error> {
+        let source = read_file(path)?;
+
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("tsx") => parse_tsx_tileset(first_gid, &parse_xml(&source)?),
+            Some("tsj") | Some("json") => parse_tsj_tileset(first_gid, &parse_json(&source)?),
+            _ => Err(Error::Unsupported(format!("unknown tileset type {}", path.display())))
+        }
+    }
+
+    fn parse_tmx_layers(&self, parent: &Element, context: &Context, map: &mut Map) -> Result<(), Error> {
+        let scale = context.scale;
+
+        for element in &parent.children {
+            match element.name.as_str() {
+                "layer" => {
+                    let data = match element.elements("data").next() {
+                        Some(data) => data,
+                        None => return Err(Error::Invalid("<layer> is missing <data>".to_string()))
+                    };
+
+                    let gids = match data.attribute("encoding") {
+                        Some("csv") => parse_csv(&data.text)?,
+                        Some("base64") => parse_base64(&data.text, data.attribute("compression"))?,
+                        Some(encoding) => return Err(Error::Unsupported(format!("encoding `{}`", encoding))),
+                        None => data.elements("tile").map(|tile| tile.number("gid").or(Ok(0))).collect::<Result<Vec<_>, Error>>()?
+                    };
+
+                    let name = element.attribute("name").unwrap_or("");
+                    let pass = layer_pass(name, element.properties().get("pass"), Pass::Collision)?;
+                    let parallax = layer_parallax(pass, element.float("parallaxx", 1.0)?, element.float("parallaxy", 1.0)?, false)?;
+
+                    self.fill_layer(&gids, context, map.add_layer(pass, context.width, parallax))?;
+                },
+                "imagelayer" => {
+                    let source = match element.elements("image").next().and_then(|image| image.attribute("source")) {
+                        Some(source) => source,
+                        None => continue
+                    };
+
+                    let name = element.attribute("name").unwrap_or("");
+                    let pass = layer_pass(name, element.properties().get("pass"), Pass::Background)?;
+                    let parallax = layer_parallax(pass, element.float("parallaxx", 1.0)?, element.float("parallaxy", 1.0)?, element.attribute("repeatx") == Some("1"))?;
+
+                    let image = Image {
+                        path: context.directory.join(source).to_string_lossy().into_owned(),
+                        x: (element.float("offsetx", 0.0)? * scale.0) as i32,
+                        y: (element.float("offsety", 0.0)? * scale.1) as i32,
+                        scale: scale.0
+                    };
+
+                    map.add_image(pass, image, parallax);
+                },
+                "objectgroup" => {
+                    for object in element.elements("object") {
+                        let kind = object.attribute("type").or(object.attribute("class")).unwrap_or("");
+
+                        map.objects.push(Object {
+                            name: object.attribute("name").unwrap_or("").to_string(),
+                            kind: kind.to_string(),
+                            x: object.float("x", 0.0)? * scale.0,
+                            y: object.float("y", 0.0)? * scale.1,
+                            width: object.float("width", 0.0)? * scale.0,
+                            height: object.float("height", 0.0)? * scale.1,
+                            properties: object.properties()
+                        });
+                    }
+                },
+                "group" => self.parse_tmx_layers(element, context, map)?,
+                _ => ()
+            }
+        }
+
+        Ok(())
+    }
+
+    fn parse_tmj_layers(&self, parent: &Value, context: &Context, map: &mut Map) -> Result<(), Error> {
+        let scale = context.scale;
+
+        for value in json_array(parent, "layers")? {
+            match value["type"].as_str() {
+                Some("tilelayer") => {
+                    let gids = match (&value["data"], value["encoding"].as_str()) {
+                        (Value::Array(data), _) => data.iter().map(json_gid).collect::<Result<Vec<_>, Error>>()?,
+                        (Value::String(data), Some("base64")) => parse_base64(data, value["compression"].as_str())?,
+                        _ => return Err(Error::Invalid("tile layer has no data".to_string()))
+                    };
+
+                    let name = value["name"].as_str().unwrap_or("");
+                    let pass = layer_pass(name, json_properties(value).get("pass"), Pass::Collision)?;
+                    let parallax = layer_parallax(pass, json_float(value, "parallaxx", 1.0), json_float(value, "parallaxy", 1.0), false)?;
+
+                    self.fill_layer(&gids, context, map.add_layer(pass, context.width, parallax))?;
+                },
+                Some("imagelayer") => {
+                    let source = match value["image"].as_str() {
+                        Some(source) if !source.is_empty() => source,
+                        _ => continue
+                    };
+
+                    let name = value["name"].as_str().unwrap_or("");
+                    let pass = layer_pass(name, json_properties(value).get("pass"), Pass::Background)?;
+                    let parallax = layer_parallax(pass, json_float(value, "parallaxx", 1.0), json_float(value, "parallaxy", 1.0), value["repeatx"].as_bool() == Some(true))?;
+
+                    let image = Image {
+                        path: context.directory.join(source).to_string_lossy().into_owned(),
+                        x: (json_float(value, "offsetx", 0.0) * scale.0) as i32,
+                        y: (json_float(value, "offsety", 0.0) * scale.1) as i32,
+                        scale: scale.0
+                    };
+
+                    map.add_image(pass, image, parallax);
+                },
+                Some("objectgroup") => {
+                    for object in json_array(value, "objects")? {
+                        let kind = object["type"].as_str().or(object["class"].as_str()).unwrap_or("");
+
+                        map.objects.push(Object {
+                            name: object["name"].as_str().unwrap_or("").to_string(),
+                            kind: kind.to_string(),
+                            x: json_float(object, "x", 0.0) * scale.0,
+                            y: json_float(object, "y", 0.0) * scale.1,
+                            width: json_float(object, "width", 0.0) * scale.0,
+                            height: json_float(object, "height", 0.0) * scale.1,
+                            properties: json_properties(object)
+                        });
+                    }
+                },
+                Some("group") => self.parse_tmj_layers(value, context, map)?,
+                _ => ()
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fill_layer(&self, gids: &[u32], context: &Context, layer: &mut ChunkedLayer<Tile>) -> Result<(), Error> {
+        let (width, height) = (context.width, context.height);
+        if gids.len() != (width * height) as usize {
+            return Err(Error::Invalid(format!("expected {} tiles in layer, found {}", width * height, gids.len())));
+        }
+
+        let mut cache: HashMap<u32, Tile> = HashMap::new();
+
+        for (i, &gid) in gids.iter().enumerate() {
+            // tiles are always drawn the way they are in the tileset.
+            if gid & FLIPPED_MASK != 0 {
+                return Err(Error::Unsupported(format!("flipped or rotated tile {}", gid & !FLIPPED_MASK)));
+            }
+
+            if gid == 0 {
+                continue;
+            }
+
+            let tile = match cache.entry(gid) {
+                Entry::Occupied(entry) => entry.get().clone(),
+                Entry::Vacant(entry) => entry.insert(self.build_tile(gid, context.tilesets)?).clone()
+            };
+
+            layer.set_tile(i as i32 % width as i32, i as i32 / width as i32, tile);
+        }
+
+        Ok(())
+    }
+
+    fn build_tile(&self, gid: u32, tilesets: &[Tileset]) -> Result<Tile, Error> {
+        let tileset = match tilesets.iter().filter(|tileset| tileset.first_gid <= gid).max_by_key(|tileset| tileset.first_gid) {
+            Some(tileset) => tileset,
+            None => return Err(Error::Invalid(format!("no tileset for tile {}", gid)))
+        };
+
+        if Path::new(&tileset.image).file_name() != Path::new(&self.image).file_name() {
+            return Err(Error::Unsupported(format!("tileset image {} is not {}", tileset.image, self.image)));
+        }
+
+        let id = gid - tileset.first_gid;
+        let mut kind = TileType::new(&gid.to_string(), tileset.source(id));
+
+        if let Some(properties) = tileset.properties.get(&id) {
+            for (name, value) in properties {
+                kind.set_property(name, value).map_err(|message| Error::Invalid(format!("tile {}: {}", gid, message)))?;
+            }
+        }
+
+        if let Some(frames) = tileset.animations.get(&id) {
+            kind.frames = frames.iter().map(|&(frame, _)| tileset.source(frame)).collect();
+            kind.frame_time = frames.first().map(|&(_, duration)| duration as f64).unwrap_or(0.0);
+        }
+
+        Ok(Tile::Defined(Rc::new(kind)))
+    }
+}
+
+
+fn read_file<P: AsRef<Path>>(path: P) -> Result<String, Error> {
+    let mut source = String::new();
+
+    File::open(path)?.read_to_string(&mut source)?;
+
+    Ok(source)
+}
+
+fn layer_pass(name: &str, pass: Option<&String>, default: Pass) -> Result<Pass, Error> {
+    match pass {
+        Some(pass) => Pass::from_name(pass).ok_or_else(|| Error::Invalid(format!("unknown pass `{}` on layer {}", pass, name))),
+        None => Ok(Pass::from_name(name).unwrap_or(default))
+    }
+}
+
+fn layer_parallax(pass: Pass, scroll_x: f32, scroll_y: f32, repeat: bool) -> Result<Parallax, Error> {
+    let parallax = Parallax::new(scroll_x, scroll_y, repeat);
+
+    if pass == Pass::Collision && !parallax.is_none() {
+        return Err(Error::Unsupported("collision layers with parallax".to_string()));
+    }
+
+    Ok(parallax)
+}
+
+fn map_edges(edges: Option<&String>) -> Result<Edges, Error> {
+    match edges {
+        Some(edges) => Edges::from_names(&edges.split_whitespace().collect::<Vec<_>>())
+            .ok_or_else(|| Error::Invalid(format!("edges `{}` are not four of `solid`, `empty` or `clamp`", edges))),
+        None => Ok(Edges::walls())
+    }
+}
+
+// Tiles are found in the image of a tileset by counting columns.
+fn tileset_columns(columns: u32) -> Result<u32, Error> {
+    if columns == 0 {
+        return Err(Error::Invalid("tileset has no columns".to_string()));
+    }
+
+    Ok(columns)
+}
+
+fn check_orientation(orientation: Option<&str>, infinite: bool) -> Result<(), Error> {
+    if orientation != Some("orthogonal") {
+        return Err(Error::Unsupported(format!("orientation `{}`", orientation.unwrap_or(""))));
+    }
+
+    if infinite {
+        return Err(Error::Unsupported("infinite maps".to_string()));
+    }
+
+    Ok(())
+}
+
+fn parse_csv(data: &str) -> Result<Vec<u32>, Error> {
+    data.split(',')
+        .map(|gid| gid.trim())
+        .filter(|gid| !gid.is_empty())
+        .map(|gid| gid.parse().map_err(|_| Error::Invalid(format!("`{}` is not a tile", gid))))
+        .collect()
+}
+
+fn parse_base64(data: &str, compression: Option<&str>) -> Result<Vec<u32>, Error> {
+    let data = data.chars().filter(|c| !c.is_whitespace()).collect::<String>();
+
+    let compressed = match STANDARD.decode(&data) {
+        Ok(bytes) => bytes,
+        Err(err) => return Err(Error::Parse(format!("base64: {}", err)))
+    };
+
+    let bytes = match compression {
+        None | Some("") => compressed,
+        Some("zlib") => decompress(ZlibDecoder::new(&compressed[..]))?,
+        Some("gzip") => decompress(GzDecoder::new(&compressed[..]))?,
+        Some(compression) => return Err(Error::Unsupported(format!("compression `{}`", compression)))
+    };
+
+    if bytes.len() % 4 != 0 {
+        return Err(Error::Invalid("tile data is not a multiple of 4 bytes".to_string()));
+    }
+
+    Ok(bytes.chunks(4).map(|b| b[0] as u32 | (b[1] as u32) << 8 | (b[2] as u32) << 16 | (b[3] as u32) << 24).collect())
+}
+
+fn decompress<R: Read>(mut reader: R) -> Result<Vec<u8>, Error> {
+    let mut bytes = Vec::new();
+
+    reader.read_to_end(&mut bytes)?;
+
+    Ok(bytes)
+}
+
+fn parse_xml(source: &str) -> Result<Element, Error> {
+    let mut stack: Vec<Element> = Vec::new();
+
+    for event in EventReader::new(source.as_bytes()) {
+        match event {
+            Ok(XmlEvent::StartElement { name, attributes, .. }) => {
+                stack.push(Element {
+                    name: name.local_name,
+                    attributes: attributes.into_iter().map(|attribute| (attribute.name.local_name, attribute.value)).collect(),
+                    children: Vec::new(),
+                    text: String::new()
+                });
+            },
+            Ok(XmlEvent::EndElement { .. }) => {
+                let element = stack.pop().unwrap();
+
+                match stack.last_mut() {
+                    Some(parent) => parent.children.push(element),
+                    None => return Ok(element)
+                }
+            },
+            Ok(XmlEvent::Characters(text)) | Ok(XmlEvent::CData(text)) => {
+                if let Some(element) = stack.last_mut() {
+                    element.text.push_str(&text);
+                }
+            },
+            Ok(_) => (),
+            Err(err) => return Err(Error::Parse(format!("xml: {}", err)))
+        }
+    }
+
+    Err(Error::Parse("xml: missing root element".to_string()))
+}
+
+fn parse_tsx_tileset(first_gid: u32, element: &Element) -> Result<Tileset, Error> {
+    let image = match element.elements("image").next().and_then(|image| image.attribute("source")) {
+        Some(image) => image.to_string(),
+        None => return Err(Error::Unsupported("tilesets without a single image".to_string()))
+    };
+
+    let mut properties = HashMap::new();
+    let mut animations = HashMap::new();
+
+    for tile in element.elements("tile") {
+        let id = tile.number("id")?;
+
+        properties.insert(id, tile.properties());
+
+        if let Some(animation) = tile.elements("animation").next() {
+            let frames = animation.elements("frame").map(|frame| Ok((frame.number("tileid")?, frame.number("duration")?)));
+
+            animations.insert(id, frames.collect::<Result<Vec<_>, Error>>()?);
+        }
+    }
+
+    Ok(Tileset {
+        first_gid: first_gid,
+        image: image,
+        columns: tileset_columns(element.number("columns")?)?,
+        tile_width: element.number("tilewidth")?,
+        tile_height: element.number("tileheight")?,
+        margin: element.number("margin").unwrap_or(0),
+        spacing: element.number("spacing").unwrap_or(0),
+        properties: properties,
+        animations: animations
+    })
+}
+
+fn parse_json(source: &str) -> Result<Value, Error> {
+    serde_json::from_str(source).map_err(|err| Error::Parse(format!("json: {}", err)))
+}
+
+fn json_number(value: &Value, name: &str) -> Result<u32, Error> {
+    match value[name].as_u64() {
+        Some(number) => Ok(number as u32),
+        None => Err(Error::Invalid(format!("`{}` is missing or not a number", name)))
+    }
+}
+
+fn json_float(value: &Value, name: &str, default: f32) -> f32 {
+    value[name].as_f64().map(|number| number as f32).unwrap_or(default)
+}
+
+fn json_gid(value: &Value) -> Result<u32, Error> {
+    match value.as_u64() {
+        Some(gid) => Ok(gid as u32),
+        None => Err(Error::Invalid(format!("`{}` is not a tile", value)))
+    }
+}
+
+fn json_array<'v>(value: &'v Value, name: &str) -> Result<&'v Vec<Value>, Error> {
+    match value[name].as_array() {
+        Some(array) => Ok(array),
+        None => Err(Error::Invalid(format!("`{}` is missing or not an array", name)))
+    }
+}
+
+fn json_properties(value: &Value) -> HashMap<String, String> {
+    let mut properties = HashMap::new();
+
+    if let Some(array) = value["properties"].as_array() {
+        for property in array {
+            if let Some(name) = property["name"].as_str() {
+                let value = match property["value"] {
+                    Value::String(ref value) => value.clone(),
+                    ref value => value.to_string()
+                };
+
+                properties.insert(name.to_string(), value);
+            }
+        }
+    }
+
+    properties
+}
+
+fn parse_tsj_tileset(first_gid: u32, value: &Value) -> Result<Tileset, Error> {
+    let image = match value["image"].as_str() {
+        Some(image) => image.to_string(),
+        None => return Err(Error::Unsupported("tilesets without a single image".to_string()))
+    };
+
+    let mut properties = HashMap::new();
+    let mut animations = HashMap::new();
+
+    if let Some(tiles) = value["tiles"].as_array() {
+        for tile in tiles {
+            let id = json_number(tile, "id")?;
+
+            properties.insert(id, json_properties(tile));
+
+            if let Some(animation) = tile["animation"].as_array() {
+                let frames = animation.iter().map(|frame| Ok((json_number(frame, "tileid")?, json_number(frame, "duration")?)));
+
+                animations.insert(id, frames.collect::<Result<Vec<_>, Error>>()?);
+            }
+        }
+    }
+
+    Ok(Tileset {
+        first_gid: first_gid,
+        image: image,
+        columns: tileset_columns(json_number(value, "columns")?)?,
+        tile_width: json_number(value, "tilewidth")?,
+        tile_height: json_number(value, "tileheight")?,
+        margin: json_number(value, "margin").unwrap_or(0),
+        spacing: json_number(value, "spacing").unwrap_or(0),
+        properties: properties,
+        animations: animations
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use sdl2::rect::Rect;
+
+    use map::{LayerData, Edge};
+    use collision::Shape;
+
+    use super::*;
+
+    const TILESET_TMX : &str = r#"<tileset firstgid="1" name="world" tilewidth="16" tileheight="16" tilecount="924" columns="33">
+  <image source="../gfx/world.png" width="528" height="448"/>
+  <tile id="1"><properties><property name="solid" type="bool" value="true"/></properties></tile>
+  <tile id="2"><animation><frame tileid="2" duration="100"/><frame tileid="3" duration="100"/></animation></tile>
+</tileset>"#;
+
+    const TILESET_TMJ : &str = r#"{"firstgid": 1, "name": "world", "image": "../gfx/world.png", "columns": 33, "tilewidth": 16, "tileheight": 16,
+  "tiles": [{"id": 1, "properties": [{"name": "solid", "type": "bool", "value": true}]},
+    {"id": 2, "animation": [{"tileid": 2, "duration": 100}, {"tileid": 3, "duration": 100}]}]}"#;
+
+    fn assert_tiles(map: &Map) {
+        let layer = map.layers()[0].tiles().unwrap();
+
+        assert!(layer.get_tile(0, 0).kind().is_none());
+
+        let background = layer.get_tile(1, 0).kind().unwrap();
+
+        assert_eq!((background.source, background.shape()), (Rect::new(0, 0, 16, 16), Shape::Empty));
+
+        let floor = layer.get_tile(0, 1).kind().unwrap();
+
+        assert_eq!((floor.source, floor.shape()), (Rect::new(16, 0, 16, 16), Shape::Solid));
+
+        assert_eq!(layer.get_tile(1, 1).kind().unwrap().source, Rect::new(0, 0, 16, 16));
+    }
+
+    fn assert_animated(importer: &Importer, tileset: Tileset) {
+        let tile = importer.build_tile(3, &[tileset]).ok().unwrap();
+        let kind = tile.kind().unwrap();
+
+        assert_eq!(kind.frames, vec![Rect::new(32, 0, 16, 16), Rect::new(48, 0, 16, 16)]);
+        assert_eq!(kind.frame_time, 100.0);
+    }
+
+    #[test]
+    fn importer_parse_tmx() {
+        let importer = Importer::new("gfx/world.png", 32, 32);
+
+        let csv = format!(r#"<?xml version="1.0" encoding="UTF-8"?>
+<map version="1.10" orientation="orthogonal" width="2" height="2" tilewidth="16" tileheight="16" infinite="0">
+  <properties><property name="edges" value="solid solid empty clamp"/></properties>
+  {}
+  <layer id="1" name="background" width="2" height="2"><data encoding="csv">
+0,1,
+2,1
+</data></layer>
+  <objectgroup id="2" name="spawns">
+    <object id="1" name="start" type="player" x="8" y="4"/>
+  </objectgroup>
+</map>"#, TILESET_TMX);
+
+        let map = importer.parse_tmx(&csv, Path::new("levels")).ok().unwrap();
+
+        assert_eq!(map.layers().len(), 1);
+        assert_eq!(map.edges.bottom, Edge::Clamp);
+        assert_eq!(map.layers()[0].pass, Pass::Background);
+
+        assert_tiles(&map);
+
+        assert_eq!(map.objects.len(), 1);
+        assert_eq!(map.objects[0].kind, "player");
+        assert_eq!((map.objects[0].x, map.objects[0].y), (16.0, 8.0));
+
+        let zlib = format!(r#"<map orientation="orthogonal" width="2" height="2" tilewidth="16" tileheight="16">
+  {}
+  <layer name="background" width="2" height="2"><data encoding="base64" compression="zlib">eJxjYGBgYARiJigNAAAwAAU=</data></layer>
+</map>"#, TILESET_TMX);
+
+        assert_tiles(&importer.parse_tmx(&zlib, Path::new("levels")).ok().unwrap());
+
+        assert_animated(&importer, parse_tsx_tileset(1, &parse_xml(TILESET_TMX).ok().unwrap()).ok().unwrap());
+
+        let other = csv.replace("../gfx/world.png", "../gfx/mario.png");
+
+        match importer.parse_tmx(&other, Path::new("levels")) {
+            Err(Error::Unsupported(_)) => (),
+            _ => panic!("expected unsupported tileset")
+        }
+
+        match importer.parse_tmx(&csv.replace("2,1\n", "2,2147483649\n"), Path::new("levels")) {
+            Err(Error::Unsupported(_)) => (),
+            _ => panic!("expected unsupported flipped tile")
+        }
+
+        match parse_tsx_tileset(1, &parse_xml(&TILESET_TMX.replace("columns=\"33\"", "columns=\"0\"")).ok().unwrap()) {
+            Err(Error::Invalid(_)) => (),
+            _ => panic!("expected invalid tileset")
+        }
+    }
+
+    #[test]
+    fn importer_parse_tmj() {
+        let importer = Importer::new("gfx/world.png", 32, 32);
+
+        let json = format!(r#"{{"orientation": "orthogonal", "width": 2, "height": 2, "tilewidth": 16, "tileheight": 16, "infinite": false,
+  "tilesets": [{}],
+  "layers": [
+    {{"type": "group", "layers": [{{"type": "tilelayer", "name": "background", "width": 2, "height": 2, "data": [0, 1, 2, 1]}}]}},
+    {{"type": "tilelayer", "name": "base64", "width": 2, "height": 2, "encoding": "base64", "data": "AAAAAAEAAAACAAAAAQAAAA=="}},
+    {{"type": "objectgroup", "objects": [{{"name": "start", "type": "player", "x": 8, "y": 4}}]}},
+    {{"type": "imagelayer", "name": "sky", "image": "sky.png", "offsety": -4, "parallaxx": 0.25, "repeatx": true}}
+  ]}}"#, TILESET_TMJ);
+
+        let map = importer.parse_tmj(&json, Path::new("levels")).ok().unwrap();
+
+        assert_eq!(map.layers().len(), 3);
+        assert_eq!(map.layers()[1].pass, Pass::Collision);
+
+        assert_eq!(map.layers()[2].pass, Pass::Background);
+        assert_eq!(map.layers()[2].parallax, Parallax::new(0.25, 1.0, true));
+
+        match map.layers()[2].data {
+            LayerData::Image(ref image) => assert_eq!((image.path.as_str(), image.y, image.scale), ("levels/sky.png", -8, 2.0)),
+            _ => panic!("expected image")
+        }
+
+        assert_tiles(&map);
+
+        assert_animated(&importer, parse_tsj_tileset(1, &parse_json(TILESET_TMJ).ok().unwrap()).ok().unwrap());
+
+        assert_eq!(map.objects.len(), 1);
+        assert_eq!((map.objects[0].x, map.objects[0].y), (16.0, 8.0));
+
+        match parse_tsj_tileset(1, &parse_json(&TILESET_TMJ.replace("\"columns\": 33", "\"columns\": 0")).ok().unwrap()) {
+            Err(Error::Invalid(_)) => (),
+            _ => panic!("expected invalid tileset")
+        }
+
+        match importer.parse_tmj(&json.replace("\"data\": [0, 1, 2, 1]", "\"data\": [0, 1]"), Path::new("levels")) {
+            Err(Error::Invalid(_)) => (),
+            _ => panic!("expected invalid layer")
+        }
+    }
+}
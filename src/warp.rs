@@ -0,0 +1,164 @@
+use sdl2::rect::Rect;
+
+use component::Input;
+use map::{Map, Object};
+
+
+// how far from where a warp is entered the player may stand, in pixels.
+const ALIGN : i32 = 2;
+
+
+// How a warp is entered, down into the top of a pipe, right into a pipe on
+// its side, or down in front of a door.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Entrance {
+    Down,
+    Right,
+    Door
+}
+
+impl Entrance {
+    pub fn from_name(name: &str) -> Option<Entrance> {
+        match name {
+            "down" => Some(Entrance::Down),
+            "right" => Some(Entrance::Right),
+            "door" => Some(Entrance::Door),
+            _ => None
+        }
+    }
+
+    // Which way the player moves while it goes in.
+    pub fn direction(&self) -> (f32, f32) {
+        match *self {
+            Entrance::Down => (0.0, 1.0),
+            Entrance::Right => (1.0, 0.0),
+            Entrance::Door => (0.0, 0.0)
+        }
+    }
+}
+
+
+// A pipe or a door, from the `warp` object that covers where the player
+// stands to go in, to the object named `to` in the level at `level`, or in
+// the same level when there is none.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Warp {
+    pub area: Rect,
+    pub entrance: Entrance,
+    pub level: Option<String>,
+    pub to: String
+}
+
+impl Warp {
+    pub fn new(object: &Object) -> Option<Warp> {
+        let entrance = match object.properties.get("enter") {
+            Some(name) => Entrance::from_name(name)?,
+            None => Entrance::Down
+        };
+
+        Some(Warp {
+            area: Rect::new(object.x as i32, object.y as i32, object.width as u32, object.height as u32),
+            entrance: entrance,
+            level: object.properties.get("level").cloned(),
+            to: object.properties.get("to")?.clone()
+        })
+    }
+
+    // Whether the player at `rect`, standing on the ground, goes in with
+    // `input`. It has to stand in the middle of a pipe or a door, or against
+    // a pipe on its side.
+    pub fn enters(&self, rect: &Rect, input: &Input) -> bool {
+        let middle = rect.x() + rect.width() as i32 / 2;
+        let standing = (rect.bottom() - self.area.bottom()).abs() <= ALIGN;
+
+        match self.entrance {
+            Entrance::Down | Entrance::Door => input.down && standing && middle >= self.area.x() && middle < self.area.right(),
+            Entrance::Right => input.right && standing && rect.right() >= self.area.right() - ALIGN && rect.x() < self.area.right()
+        }
+    }
+
+    // The part of `view` in which the player is still drawn while it goes
+    // in, so that it disappears into the pipe.
+    pub fn visible(&self, view: &Rect) -> Option<Rect> {
+        match self.entrance {
+            Entrance::Down => Some(Rect::new(view.x(), view.y(), view.width(), (self.area.bottom() - view.y()).max(0) as u32)),
+            Entrance::Right => Some(Rect::new(view.x(), view.y(), (self.area.right() - view.x()).max(0) as u32, view.height())),
+            Entrance::Door => None
+        }
+    }
+}
+
+
+// The `area` object of `map` that `x`, `y` is in, which the camera keeps
+// to, or the whole map when it is in none.
+pub fn bounds(map: &Map, x: f32, y: f32) -> Rect {
+    let area = map.objects.iter().find(|object| {
+        object.kind == "area" && x >= object.x && x < object.x + object.width && y >= object.y && y < object.y + object.height
+    });
+
+    match area {
+        Some(area) => Rect::new(area.x as i32, area.y as i32, area.width as u32, area.height as u32),
+        None => map.to_rect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn object(kind: &str, x: f32, y: f32, width: f32, height: f32, properties: &[(&str, &str)]) -> Object {
+        Object {
+            name: String::new(),
+            kind: kind.to_string(),
+            x: x,
+            y: y,
+            width: width,
+            height: height,
+            properties: properties.iter().map(|&(name, value)| (name.to_string(), value.to_string())).collect::<HashMap<_, _>>()
+        }
+    }
+
+    #[test]
+    fn warp_enters() {
+        // the top of a pipe two tiles wide.
+        let pipe = Warp::new(&object("warp", 64.0, 96.0, 64.0, 32.0, &[("to", "cellar")])).unwrap();
+
+        assert_eq!((pipe.entrance, pipe.level.clone(), pipe.to.as_str()), (Entrance::Down, None, "cellar"));
+
+        let down = Input { down: true, ..Input::default() };
+
+        assert!(pipe.enters(&Rect::new(80, 96, 32, 32), &down));
+        assert!(pipe.enters(&Rect::new(80, 64, 32, 64), &down));
+        assert!(!pipe.enters(&Rect::new(80, 96, 32, 32), &Input::default()));
+        assert!(!pipe.enters(&Rect::new(40, 96, 32, 32), &down));
+        assert!(!pipe.enters(&Rect::new(80, 80, 32, 32), &down));
+
+        // a pipe on its side, to the right of the warp.
+        let side = Warp::new(&object("warp", 64.0, 64.0, 32.0, 64.0, &[("enter", "right"), ("level", "levels/1-1.txt"), ("to", "pipe")])).unwrap();
+        let right = Input { right: true, ..Input::default() };
+
+        assert!(side.enters(&Rect::new(64, 96, 32, 32), &right));
+        assert!(!side.enters(&Rect::new(40, 96, 32, 32), &right));
+        assert!(!side.enters(&Rect::new(64, 96, 32, 32), &down));
+
+        assert_eq!(side.visible(&Rect::new(0, 0, 200, 100)), Some(Rect::new(0, 0, 96, 100)));
+        assert_eq!(pipe.visible(&Rect::new(0, 100, 200, 100)), Some(Rect::new(0, 100, 200, 28)));
+
+        assert_eq!(Warp::new(&object("warp", 0.0, 0.0, 32.0, 32.0, &[("enter", "up"), ("to", "cellar")])), None);
+        assert_eq!(Warp::new(&object("warp", 0.0, 0.0, 32.0, 32.0, &[])), None);
+    }
+
+    #[test]
+    fn warp_bounds() {
+        let mut map = Map::new(40, 10, 32, 32);
+
+        map.objects.push(object("area", 0.0, 0.0, 960.0, 320.0, &[]));
+        map.objects.push(object("area", 960.0, 0.0, 320.0, 320.0, &[]));
+
+        assert_eq!(bounds(&map, 100.0, 100.0), Rect::new(0, 0, 960, 320));
+        assert_eq!(bounds(&map, 1000.0, 100.0), Rect::new(960, 0, 320, 320));
+        assert_eq!(bounds(&map, 100.0, 400.0), Rect::new(0, 0, 1280, 320));
+    }
+}
@@ -0,0 +1,647 @@
+use std::mem;
+
+use sdl2::rect::Rect;
+use sdl2::render::Renderer;
+
+use game_object::{GameObject, Animation, Pose, Power};
+use component::{TICK, Updatable, Context, Event, Touch, Transform, Velocity, Blocked, Health, Actor, Look};
+use item_components::Pickup;
+use camera::Camera;
+use contact::{self, Collider};
+use collision;
+use map::Map;
+
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Entity {
+    index: usize,
+    generation: u32
+}
+
+
+// One kind of component, kept for every entity at the index of the entity.
+struct Storage<T> {
+    items: Vec<Option<T>>
+}
+
+impl<T> Storage<T> {
+    fn new() -> Storage<T> {
+        Storage {
+            items: Vec::new()
+        }
+    }
+
+    fn insert(&mut self, index: usize, item: Option<T>) {
+        while self.items.len() <= index {
+            self.items.push(None);
+        }
+
+        self.items[index] = item;
+    }
+
+    fn get(&self, index: usize) -> Option<&T> {
+        self.items.get(index).and_then(|item| item.as_ref())
+    }
+
+    fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.items.get_mut(index).and_then(|item| item.as_mut())
+    }
+}
+
+
+struct Slot {
+    generation: u32,
+    alive: bool,
+    removed: bool
+}
+
+
+// All the objects in a level, as entities made of components that systems
+// run over in a fixed order every tick. Spawning and destroying are queued
+// and only happen between ticks, so that systems never see half of a change.
+pub struct World<'a> {
+    cell_size: u32,
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+    spawned: Vec<(Entity, GameObject<'a>)>,
+    destroyed: Vec<Entity>,
+    transforms: Storage<Transform>,
+    velocities: Storage<Velocity>,
+    colliders: Storage<Collider>,
+    animations: Storage<Animation<'a>>,
+    ais: Storage<Box<dyn Updatable + 'a>>,
+    healths: Storage<Health>,
+    pickups: Storage<Pickup>
+}
+
+impl<'a> World<'a> {
+    // `cell_size` is the size of the cells used to find objects that touch,
+    // it works best when objects are about as large.
+    pub fn new(cell_size: u32) -> World<'a> {
+        World {
+            cell_size: cell_size,
+            slots: Vec::new(),
+            free: Vec::new(),
+            spawned: Vec::new(),
+            destroyed: Vec::new(),
+            transforms: Storage::new(),
+            velocities: Storage::new(),
+            colliders: Storage::new(),
+            animations: Storage::new(),
+            ais: Storage::new(),
+            healths: Storage::new(),
+            pickups: Storage::new()
+        }
+    }
+
+    pub fn spawn(&mut self, object: GameObject<'a>) -> Entity {
+        let index = match self.free.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot { generation: 0, alive: false, removed: false });
+
+                self.slots.len() - 1
+            }
+        };
+
+        let entity = Entity { index: index, generation: self.slots[index].generation };
+
+        self.spawned.push((entity, object));
+
+        entity
+    }
+
+    // Entities that are still waiting to be spawned never are.
+    pub fn destroy(&mut self, entity: Entity) {
+        let pending = self.spawned.len();
+
+        self.spawned.retain(|&(spawned, _)| spawned != entity);
+
+        if self.spawned.len() < pending {
+            self.slots[entity.index].generation += 1;
+            self.free.push(entity.index);
+        } else {
+            self.destroyed.push(entity);
+        }
+    }
+
+    fn index(&self, entity: Entity) -> Option<usize> {
+        match self.slots.get(entity.index) {
+            Some(slot) if slot.alive && slot.generation == entity.generation => Some(entity.index),
+            _ => None
+        }
+    }
+
+    // Whether `entity` is in the world, or is about to be spawned.
+    pub fn exists(&self, entity: Entity) -> bool {
+        self.index(entity).is_some() || self.spawned.iter().any(|&(spawned, _)| spawned == entity)
+    }
+
+    pub fn entities(&self) -> Vec<Entity> {
+        self.slots.iter().enumerate()
+            .filter(|&(_, slot)| slot.alive)
+            .map(|(index, slot)| Entity { index: index, generation: slot.generation })
+            .collect()
+    }
+
+    pub fn transform(&self, entity: Entity) -> Option<&Transform> {
+        self.index(entity).and_then(|index| self.transforms.get(index))
+    }
+
+    pub fn transform_mut(&mut self, entity: Entity) -> Option<&mut Transform> {
+        match self.index(entity) {
+            Some(index) => self.transforms.get_mut(index),
+            None => None
+        }
+    }
+
+    pub fn velocity(&self, entity: Entity) -> Option<&Velocity> {
+        self.index(entity).and_then(|index| self.velocities.get(index))
+    }
+
+    pub fn velocity_mut(&mut self, entity: Entity) -> Option<&mut Velocity> {
+        match self.index(entity) {
+            Some(index) => self.velocities.get_mut(index),
+            None => None
+        }
+    }
+
+    #[cfg(test)]
+    pub fn collider(&self, entity: Entity) -> Option<Collider> {
+        self.index(entity).and_then(|index| self.colliders.get(index).cloned())
+    }
+
+    pub fn set_collider(&mut self, entity: Entity, collider: Option<Collider>) {
+        if let Some(index) = self.index(entity) {
+            self.colliders.insert(index, collider);
+        }
+    }
+
+    pub fn pose(&self, entity: Entity) -> Option<Pose> {
+        self.index(entity).and_then(|index| self.animations.get(index)).map(|animation| animation.pose)
+    }
+
+    pub fn set_pose(&mut self, entity: Entity, pose: Pose) {
+        if let Some(animation) = self.index(entity).and_then(move |index| self.animations.get_mut(index)) {
+            animation.pose = pose;
+        }
+    }
+
+    pub fn health(&self, entity: Entity) -> Option<&Health> {
+        self.index(entity).and_then(|index| self.healths.get(index))
+    }
+
+    pub fn health_mut(&mut self, entity: Entity) -> Option<&mut Health> {
+        match self.index(entity) {
+            Some(index) => self.healths.get_mut(index),
+            None => None
+        }
+    }
+
+    // Runs `f` with the AI of the entity at `index` and the parts of the
+    // entity that it looks after, if it has all of them. The AI is taken out
+    // while it runs.
+    fn run<R, F>(&mut self, index: usize, f: F) -> Option<R> where F: FnOnce(&mut (dyn Updatable + 'a), &mut Actor) -> R {
+        let mut ai = self.ais.items.get_mut(index).and_then(|ai| ai.take())?;
+
+        let result = match (self.transforms.get_mut(index), self.velocities.get_mut(index), self.animations.get_mut(index)) {
+            (Some(transform), Some(velocity), Some(animation)) => {
+                let mut actor = Actor {
+                    transform: transform,
+                    velocity: velocity,
+                    collider: &mut self.colliders.items[index],
+                    pose: &mut animation.pose,
+                    health: self.healths.get_mut(index),
+                    removed: &mut self.slots[index].removed
+                };
+
+                Some(f(&mut *ai, &mut actor))
+            },
+            _ => None
+        };
+
+        self.ais.items[index] = Some(ai);
+
+        result
+    }
+
+    // Runs one tick of every system, in order, and returns what happened to
+    // which entity.
+    pub fn update(&mut self, context: &Context) -> Vec<(Entity, Event)> {
+        let mut events = Vec::new();
+
+        self.maintain();
+
+        self.think(context, &mut events);
+        self.physics(context, &mut events);
+
+        let touches = self.find_touches();
+
+        self.react(&touches, context, &mut events);
+        self.pickup(&touches, &mut events);
+        self.damage(context.dt, &mut events);
+        self.cleanup(context.map);
+
+        self.maintain();
+
+        events
+    }
+
+    // The AI system, which lets every AI decide what its object does.
+    fn think(&mut self, context: &Context, events: &mut Vec<(Entity, Event)>) {
+        for entity in self.entities() {
+            if let Some(emitted) = self.run(entity.index, |ai, actor| ai.update(actor, context)) {
+                events.extend(emitted.into_iter().map(|event| (entity, event)));
+            }
+        }
+    }
+
+    // The physics system, which moves everything that has a velocity.
+    fn physics(&mut self, context: &Context, events: &mut Vec<(Entity, Event)>) {
+        let scale = (context.dt / TICK) as f32;
+
+        for entity in self.entities() {
+            if let (Some(transform), Some(velocity)) = (self.transforms.get_mut(entity.index), self.velocities.get_mut(entity.index)) {
+                events.extend(step(transform, velocity, context.map, scale).into_iter().map(|event| (entity, event)));
+            }
+        }
+    }
+
+    // Finds the objects that touch, and how each of them is touched.
+    fn find_touches(&self) -> Vec<(Entity, Touch)> {
+        let bodies = self.entities().into_iter().filter_map(|entity| {
+            match (self.transforms.get(entity.index), self.colliders.get(entity.index)) {
+                (Some(transform), Some(&collider)) if !self.slots[entity.index].removed => Some((entity, transform.to_rect(), collider)),
+                _ => None
+            }
+        }).collect::<Vec<_>>();
+
+        let touch = |entity: Entity, side| Touch {
+            entity: Some(entity),
+            side: side,
+            layer: self.colliders.get(entity.index).map(|collider| collider.layer).unwrap_or(0),
+            rect: self.transforms.get(entity.index).map(|transform| transform.to_rect()).unwrap_or(Rect::new(0, 0, 1, 1))
+        };
+
+        contact::find_contacts(&bodies, self.cell_size).into_iter().flat_map(|contact| {
+            vec![(contact.a, touch(contact.b, contact.side)), (contact.b, touch(contact.a, contact.side.opposite()))]
+        }).collect()
+    }
+
+    // The contact system, which lets the AI of each object react to what it
+    // touched, unless an earlier touch took it out of play.
+    fn react(&mut self, touches: &[(Entity, Touch)], context: &Context, events: &mut Vec<(Entity, Event)>) {
+        for &(entity, ref touch) in touches {
+            if self.colliders.get(entity.index).is_none() || self.slots[entity.index].removed {
+                continue;
+            }
+
+            if let Some(other) = touch.entity {
+                events.push((entity, Event::Touched(other, touch.side)));
+            }
+
+            if let Some(emitted) = self.run(entity.index, |ai, actor| ai.touch(actor, touch, context)) {
+                events.extend(emitted.into_iter().map(|event| (entity, event)));
+            }
+        }
+    }
+
+    // The pickup system, which hands the pickups that were touched to what
+    // touched them.
+    fn pickup(&mut self, touches: &[(Entity, Touch)], events: &mut Vec<(Entity, Event)>) {
+        for &(entity, ref touch) in touches {
+            let item = match self.pickups.get(entity.index) {
+                Some(pickup) if pickup.mask & touch.layer != 0 && !self.slots[entity.index].removed => pickup.item,
+                _ => continue
+            };
+
+            self.slots[entity.index].removed = true;
+            self.colliders.insert(entity.index, None);
+
+            events.push((entity, Event::Collected(item)));
+
+            if let Some(collector) = touch.entity.and_then(|other| self.index(other)) {
+                self.run(collector, |ai, actor| ai.collect(actor, item));
+            }
+        }
+    }
+
+    // The health system, which hurts and kills what was hit.
+    fn damage(&mut self, dt: f64, events: &mut Vec<(Entity, Event)>) {
+        for entity in self.entities() {
+            let event = match self.healths.get_mut(entity.index).and_then(|health| health.update(dt)) {
+                Some(event) => event,
+                None => continue
+            };
+
+            if event == Event::Killed {
+                self.run(entity.index, |ai, actor| ai.kill(actor));
+            } else {
+                self.run(entity.index, |ai, actor| ai.hurt(actor));
+            }
+
+            events.push((entity, event));
+        }
+    }
+
+    // The cleanup system, which destroys what was removed and what fell off
+    // the bottom of the map. Objects with health are left to die instead.
+    fn cleanup(&mut self, map: &Map) {
+        let bottom = map.to_rect().bottom() as f32;
+
+        for entity in self.entities() {
+            let fallen = self.healths.get(entity.index).is_none() && self.transforms.get(entity.index).map(|transform| transform.y > bottom).unwrap_or(false);
+
+            if self.slots[entity.index].removed || fallen {
+                self.destroyed.push(entity);
+            }
+        }
+    }
+
+    // Lets `entity` react to `touch`, as if it touched something during a
+    // tick.
+    #[cfg(test)]
+    pub fn touch(&mut self, entity: Entity, touch: &Touch, context: &Context) -> Vec<(Entity, Event)> {
+        if self.index(entity).is_none() {
+            return Vec::new();
+        }
+
+        self.respond(&[(entity, *touch)], context)
+    }
+
+    // Lets the objects in `area` that look for the layer of `touch` react to
+    // it, for touches by the map rather than by another object.
+    pub fn touch_area(&mut self, area: &Rect, touch: &Touch, context: &Context) -> Vec<(Entity, Event)> {
+        let touches = self.entities().into_iter().filter(|entity| {
+            match (self.transforms.get(entity.index), self.colliders.get(entity.index)) {
+                (Some(transform), Some(collider)) => collider.mask & touch.layer != 0 && !self.slots[entity.index].removed && transform.to_rect().has_intersection(*area),
+                _ => false
+            }
+        }).map(|entity| (entity, *touch)).collect::<Vec<_>>();
+
+        self.respond(&touches, context)
+    }
+
+    // Runs the systems that follow touches, outside of a tick.
+    fn respond(&mut self, touches: &[(Entity, Touch)], context: &Context) -> Vec<(Entity, Event)> {
+        let mut events = Vec::new();
+
+        self.react(touches, context, &mut events);
+        self.pickup(touches, &mut events);
+        self.damage(0.0, &mut events);
+
+        events
+    }
+
+    // The render system, which draws everything that has an animation.
+    pub fn render(&mut self, elapsed: f64, renderer: &mut Renderer, camera: &Camera) {
+        for entity in self.entities() {
+            let index = entity.index;
+
+            let (transform, animation) = match (self.transforms.get(index), self.animations.get_mut(index)) {
+                (Some(transform), Some(animation)) => (transform, animation),
+                _ => continue
+            };
+
+            let velocity = self.velocities.get(index);
+
+            let look = Look {
+                pose: animation.pose,
+                power: self.healths.get(index).map(|health| health.power).unwrap_or(Power::Small),
+                facing: transform.facing,
+                h: transform.h,
+                dx: velocity.map(|velocity| velocity.dx).unwrap_or(0.0),
+                on_ground: velocity.map(|velocity| velocity.on_ground).unwrap_or(false)
+            };
+
+            animation.render(&look, elapsed, renderer, &camera.to_relative_rect(&transform.to_rect()));
+        }
+    }
+
+    // Applies the queued spawns and destroys.
+    pub fn maintain(&mut self) {
+        for entity in mem::take(&mut self.destroyed) {
+            if self.index(entity).is_none() {
+                continue;
+            }
+
+            let index = entity.index;
+
+            self.slots[index].alive = false;
+            self.slots[index].generation += 1;
+
+            self.transforms.insert(index, None);
+            self.velocities.insert(index, None);
+            self.colliders.insert(index, None);
+            self.animations.insert(index, None);
+            self.ais.insert(index, None);
+            self.healths.insert(index, None);
+            self.pickups.insert(index, None);
+
+            self.free.push(index);
+        }
+
+        for (entity, object) in mem::take(&mut self.spawned) {
+            let index = entity.index;
+
+            self.slots[index].alive = true;
+            self.slots[index].removed = false;
+
+            self.transforms.insert(index, Some(object.transform));
+            self.velocities.insert(index, object.velocity);
+            self.colliders.insert(index, object.collider);
+            self.animations.insert(index, object.animation);
+            self.ais.insert(index, object.ai);
+            self.healths.insert(index, object.health);
+            self.pickups.insert(index, object.pickup);
+        }
+    }
+}
+
+
+// Moves `transform` by `velocity` for `scale` ticks. Solid objects are
+// stopped by the map, bump the tiles they hit from below and bounce off
+// bouncy ones.
+fn step(transform: &mut Transform, velocity: &mut Velocity, map: &Map, scale: f32) -> Vec<Event> {
+    let mut events = Vec::new();
+
+    velocity.dy = (velocity.dy + velocity.gravity * scale).min(velocity.terminal);
+
+    if !velocity.solid {
+        transform.x += velocity.dx * scale;
+        transform.y += velocity.dy * scale;
+
+        velocity.blocked = Blocked::default();
+
+        return events;
+    }
+
+    let falling = velocity.dy;
+
+    let result = collision::move_and_collide(map, &transform.to_body(), velocity.dx * scale, velocity.dy * scale, velocity.drop_through);
+
+    if result.bottom && !velocity.on_ground {
+        events.push(Event::Landed);
+    }
+
+    transform.x = result.x;
+    transform.y = result.y;
+
+    if result.left || result.right {
+        velocity.dx = 0.0;
+    }
+
+    if result.top || result.bottom {
+        velocity.dy = 0.0;
+    }
+
+    velocity.drop_through = false;
+    velocity.on_ground = result.bottom;
+    velocity.blocked = Blocked { left: result.left, right: result.right, top: result.top, bottom: result.bottom };
+
+    if result.top {
+        // bumps the tile above that is closest to its middle.
+        let row = (transform.y / map.tile_height() as f32).floor() as i32 - 1;
+        let middle = transform.x + transform.w as f32 / 2.0;
+        let tile_width = map.tile_width() as f32;
+
+        let column = result.tiles.iter()
+            .filter(|&&(_, y)| y == row)
+            .map(|&(x, _)| x)
+            .min_by_key(|&x| ((x as f32 + 0.5) * tile_width - middle).abs() as i32);
+
+        if let Some(column) = column {
+            events.push(Event::Bumped(column, row));
+        }
+    }
+
+    if result.bottom {
+        let bounciness = map.types_at(&collision::below(&transform.to_rect())).iter().map(|kind| kind.bounciness).fold(0.0, f32::max);
+
+        if bounciness > 0.0 && falling > 1.0 {
+            velocity.dy = -falling * bounciness;
+            velocity.on_ground = false;
+        }
+    }
+
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use component::Input;
+    use contact::{Collider, Side, PLAYER, ENEMY};
+    use map::Map;
+    use test_util::Invisible;
+
+    use super::*;
+
+    // is removed once it reaches y = 2.
+    struct Faller;
+
+    impl Updatable for Faller {
+        fn update(&mut self, actor: &mut Actor, _: &Context) -> Vec<Event> {
+            *actor.removed = actor.transform.y >= 2.0;
+
+            vec![Event::Landed]
+        }
+    }
+
+    // falls one pixel per tick.
+    fn faller<'a>(y: f32) -> GameObject<'a> {
+        GameObject::new(0.0, y)
+            .with_velocity(Velocity { gravity: 0.0, solid: false, ..Velocity::new(0.0, 1.0) })
+            .with_graphics(Box::new(Invisible))
+            .with_ai(Box::new(Faller))
+    }
+
+    #[test]
+    fn world_spawn_destroy() {
+        let mut world = World::new(10);
+
+        let a = world.spawn(faller(0.0));
+        let b = world.spawn(faller(0.0));
+
+        assert!(world.transform(a).is_none());
+
+        world.maintain();
+
+        assert_eq!(world.entities(), vec![a, b]);
+
+        world.destroy(a);
+        world.maintain();
+
+        assert!(world.transform(a).is_none());
+        assert!(world.transform(b).is_some());
+
+        // the slot is reused, but not the old handle.
+        let c = world.spawn(faller(0.0));
+
+        world.maintain();
+
+        assert!(c != a);
+        assert!(world.transform(a).is_none());
+        assert_eq!(world.entities(), vec![c, b]);
+
+        // is destroyed before it was ever spawned.
+        let d = world.spawn(faller(0.0));
+
+        world.destroy(d);
+
+        assert!(!world.exists(d));
+
+        world.maintain();
+
+        assert!(!world.exists(d));
+        assert_eq!(world.entities(), vec![c, b]);
+
+        let e = world.spawn(faller(0.0));
+
+        world.maintain();
+
+        assert!(e != d);
+        assert!(!world.exists(d));
+    }
+
+    #[test]
+    fn world_update() {
+        let map = Map::new(1, 1, 10, 10);
+        let context = Context { map: &map, input: Input::default(), player: None, dt: 10.0 };
+
+        let mut world = World::new(10);
+
+        let a = world.spawn(faller(0.0));
+        let b = world.spawn(faller(2.0));
+
+        assert_eq!(world.update(&context), vec![(a, Event::Landed), (b, Event::Landed)]);
+
+        assert_eq!(world.transform(a).unwrap().y, 1.0);
+        assert!(world.transform(b).is_none());
+
+        world.update(&context);
+        world.update(&context);
+
+        assert!(world.entities().is_empty());
+    }
+
+    #[test]
+    fn world_contacts() {
+        let map = Map::new(1, 1, 10, 10);
+        let context = Context { map: &map, input: Input::default(), player: None, dt: 10.0 };
+
+        let mut world = World::new(10);
+
+        let a = world.spawn(faller(-30.0).with_collider(Collider::new(PLAYER, ENEMY)));
+        let b = world.spawn(faller(0.0).with_collider(Collider::new(ENEMY, PLAYER)));
+
+        world.spawn(faller(-30.0));
+
+        // the first lands on top of the second, the third has no collider.
+        let events = world.update(&context);
+
+        assert_eq!(events.iter().filter(|&&(_, event)| event != Event::Landed).collect::<Vec<_>>(), vec![
+            &(a, Event::Touched(b, Side::Top)),
+            &(b, Event::Touched(a, Side::Bottom))
+        ]);
+    }
+}